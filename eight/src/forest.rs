@@ -0,0 +1,209 @@
+use std::cmp::Reverse;
+use std::io;
+use std::io::ErrorKind;
+use std::str::FromStr;
+use tracing::{info_span, trace};
+use crate::grid::{Grid, Line, Point, Step, EAST, NORTH, NORTH_EAST, NORTH_WEST, SOUTH, SOUTH_EAST, SOUTH_WEST, WEST};
+
+/// marks every tree taller than all the trees before it on the line
+fn mark_visible<'a, T: Ord + 'a>(visible: &mut Grid<bool>, line: impl Iterator<Item = (Point, &'a T)>) {
+    let mut tallest: Option<&T> = None;
+    for (point, tree) in line {
+        if tallest.is_none_or(|tallest| tree > tallest) {
+            trace!(?point, "visible");
+            visible[point] = true;
+            tallest = Some(tree);
+        }
+    }
+}
+
+/// Calls `record` with how many trees each tree on the line can see looking back toward the start
+/// of the line, stopping at the first tree at least as tall as itself. A stack of (height, trees
+/// hidden behind it) keeps this linear: a tree that is overtaken is never looked at again.
+fn viewing_distances<'a, T: Ord + 'a>(line: impl Iterator<Item = (Point, &'a T)>, mut record: impl FnMut(Point, usize)) {
+    let mut sightlines: Vec<(&T, usize)> = Vec::new();
+    for (point, tree) in line {
+        let mut distance = 0;
+        let mut hidden = 1;
+        while let Some((previous, count)) = sightlines.pop() {
+            if tree > previous {
+                distance += count;
+                hidden += count;
+            } else {
+                distance += 1;
+                sightlines.push((previous, count));
+                break;
+            }
+        }
+        sightlines.push((tree, hidden));
+        trace!(?point, distance, "viewing distance");
+        record(point, distance);
+    }
+}
+
+/// up, down, left and right, the directions of the original puzzle
+pub const ORTHOGONAL: [Step; 4] = [NORTH, SOUTH, WEST, EAST];
+pub const DIAGONAL: [Step; 4] = [NORTH_EAST, NORTH_WEST, SOUTH_EAST, SOUTH_WEST];
+/// all eight compass directions
+pub const COMPASS: [Step; 8] = [NORTH, NORTH_EAST, EAST, SOUTH_EAST, SOUTH, SOUTH_WEST, WEST, NORTH_WEST];
+
+/// a compass name for the eight unit directions, and the vector itself for any other step
+pub fn direction_name(step: Step) -> String {
+    let name = match step {
+        NORTH => "N",
+        NORTH_EAST => "NE",
+        EAST => "E",
+        SOUTH_EAST => "SE",
+        SOUTH => "S",
+        SOUTH_WEST => "SW",
+        WEST => "W",
+        NORTH_WEST => "NW",
+        (rows, columns) => return format!("({},{})", rows, columns),
+    };
+    name.to_string()
+}
+
+/// Every line that looks back in direction `looking`: it walks the opposite way, starting at the trees
+/// with nothing in front of them, so each tree is on exactly one line and the trees it looks at come
+/// before it. For a step like (2, 1) the trees between lattice points are not on the line.
+fn lines_looking<T>(grid: &Grid<T>, looking: Step) -> impl Iterator<Item = Line<'_, T>> + '_ {
+    let walk = (-looking.0, -looking.1);
+    grid.points().filter(move |point| grid.offset(*point, looking).is_none()).map(move |start| grid.line(start, walk))
+}
+
+/// Visibility and viewing distances of every tree, one grid of each per direction
+pub struct Analysis {
+    pub directions: Vec<Step>,
+    /// `visible[i]` marks the trees visible from outside the grid looking back along `directions[i]`,
+    /// i.e. every tree in that direction is shorter
+    pub visible: Vec<Grid<bool>>,
+    /// `distances[i]` counts the trees each tree sees looking along `directions[i]`, up to and
+    /// including the first one at least as tall as itself
+    pub distances: Vec<Grid<usize>>,
+}
+
+impl Analysis {
+    /// trees visible from at least one of the directions
+    pub fn visible_from_any(&self) -> Grid<bool> {
+        let mut any = self.visible[0].clone();
+        for visible in &self.visible[1..] {
+            for (point, cell) in any.iter_mut() {
+                *cell |= visible[point];
+            }
+        }
+        any
+    }
+
+    /// the product of the viewing distances in every direction; fails if a product doesn't fit a `usize`
+    pub fn scenic_scores(&self) -> io::Result<Grid<usize>> {
+        let mut scores = self.distances[0].clone();
+        for distances in &self.distances[1..] {
+            for (point, score) in scores.iter_mut() {
+                *score = score.checked_mul(distances[point]).ok_or_else(|| {
+                    io::Error::new(ErrorKind::InvalidData, format!("the scenic score of the tree at {:?} overflows", point))
+                })?;
+            }
+        }
+        Ok(scores)
+    }
+
+    /// the `k` best treehouse locations, best first
+    pub fn best_locations(&self, k: usize, tie_break: TieBreak) -> io::Result<Vec<Location>> {
+        let scores = self.scenic_scores()?;
+        Ok(top_locations(&scores, k, tie_break)
+            .into_iter()
+            .map(|position| Location {
+                position,
+                score: scores[position],
+                distances: self.directions.iter().zip(&self.distances).map(|(direction, distances)| (*direction, distances[position])).collect(),
+            })
+            .collect())
+    }
+}
+
+/// Analyses the forest along each of `directions`, which may be any non-zero integer steps
+pub fn analyse<T: Ord>(grid: &Grid<T>, directions: &[Step]) -> io::Result<Analysis> {
+    if directions.is_empty() || directions.contains(&(0, 0)) {
+        return Err(io::Error::new(ErrorKind::InvalidInput, "directions must be non-empty and not (0, 0)"));
+    }
+    let mut visible = Vec::with_capacity(directions.len());
+    let mut distances = Vec::with_capacity(directions.len());
+    for &direction in directions {
+        let _direction = info_span!("direction", direction = %direction_name(direction)).entered();
+        let mut direction_visible = Grid::filled(grid.width(), grid.height(), false);
+        info_span!("visibility").in_scope(|| {
+            for line in lines_looking(grid, direction) {
+                mark_visible(&mut direction_visible, line);
+            }
+        });
+        let mut direction_distances = Grid::filled(grid.width(), grid.height(), 0);
+        info_span!("scenic").in_scope(|| {
+            for line in lines_looking(grid, direction) {
+                viewing_distances(line, |point, distance| direction_distances[point] = distance);
+            }
+        });
+        visible.push(direction_visible);
+        distances.push(direction_distances);
+    }
+    Ok(Analysis { directions: directions.to_vec(), visible, distances })
+}
+
+/// trees visible from outside the grid along any row or column
+pub fn visible_trees<T: Ord>(grid: &Grid<T>) -> Grid<bool> {
+    analyse(grid, &ORTHOGONAL).expect("the orthogonal directions are valid").visible_from_any()
+}
+
+/// the product of the viewing distances up, down, left and right from every tree
+pub fn scenic_scores<T: Ord>(grid: &Grid<T>) -> io::Result<Grid<usize>> {
+    analyse(grid, &ORTHOGONAL)?.scenic_scores()
+}
+
+/// How `top_locations` orders trees with the same score
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TieBreak {
+    /// the earlier row, then the earlier column
+    ReadingOrder,
+    /// the tree closest to the centre of the forest, then reading order
+    Centre,
+}
+
+impl FromStr for TieBreak {
+    type Err = io::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "reading" => Ok(TieBreak::ReadingOrder),
+            "centre" | "center" => Ok(TieBreak::Centre),
+            _ => Err(io::Error::new(ErrorKind::InvalidInput, "tie break must be reading or centre")),
+        }
+    }
+}
+
+/// The `k` highest scoring points, best first, with ties ordered by `tie_break`
+pub fn top_locations(scores: &Grid<usize>, k: usize, tie_break: TieBreak) -> Vec<Point> {
+    // twice the distance from the centre, squared, so it stays an integer for even sizes
+    let (height, width) = (scores.height() as i64, scores.width() as i64);
+    let centre_distance = |(row, column): Point| (2 * row as i64 - (height - 1)).pow(2) + (2 * column as i64 - (width - 1)).pow(2);
+    let key = |(point, score): (Point, usize)| {
+        let tie = match tie_break {
+            TieBreak::ReadingOrder => 0,
+            TieBreak::Centre => centre_distance(point),
+        };
+        (Reverse(score), tie, point)
+    };
+    let mut ranked: Vec<_> = scores.iter().map(|(point, score)| key((point, *score))).collect();
+    if k < ranked.len() {
+        ranked.select_nth_unstable(k);
+        ranked.truncate(k);
+    }
+    ranked.sort_unstable();
+    ranked.into_iter().map(|(_, _, point)| point).collect()
+}
+
+/// A candidate treehouse: its score and how far it can see in each analysed direction
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Location {
+    pub position: Point,
+    pub score: usize,
+    pub distances: Vec<(Step, usize)>,
+}
@@ -0,0 +1,355 @@
+use std::fmt;
+use std::io;
+use std::io::ErrorKind;
+use std::ops::{Index, IndexMut};
+use std::str::FromStr;
+
+/// (row, column), with (0, 0) in the top left corner
+pub type Point = (usize, usize);
+
+/// (row step, column step) between consecutive points of a line
+pub type Step = (isize, isize);
+
+pub const NORTH: Step = (-1, 0);
+pub const SOUTH: Step = (1, 0);
+pub const EAST: Step = (0, 1);
+pub const WEST: Step = (0, -1);
+pub const NORTH_EAST: Step = (-1, 1);
+pub const NORTH_WEST: Step = (-1, -1);
+pub const SOUTH_EAST: Step = (1, 1);
+pub const SOUTH_WEST: Step = (1, -1);
+
+/// A rectangle of cells stored row by row in one `Vec`
+#[derive(Clone, PartialEq, Eq)]
+pub struct Grid<T> {
+    cells: Vec<T>,
+    width: usize,
+    height: usize,
+}
+
+impl<T> Grid<T> {
+    /// a grid from cells in row-major order; fails unless there are exactly `width * height` of them
+    pub fn from_cells(width: usize, height: usize, cells: Vec<T>) -> io::Result<Grid<T>> {
+        if cells.len() != width * height {
+            let message = format!("{} cells don't make a {}x{} grid", cells.len(), width, height);
+            return Err(io::Error::new(ErrorKind::InvalidData, message));
+        }
+        Ok(Grid { cells, width, height })
+    }
+
+    pub fn from_fn(width: usize, height: usize, mut cell: impl FnMut(Point) -> T) -> Grid<T> {
+        let cells = (0..height).flat_map(|row| (0..width).map(move |column| (row, column))).map(&mut cell).collect();
+        Grid { cells, width, height }
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    /// the cells in row-major order
+    pub fn cells(&self) -> &[T] {
+        &self.cells
+    }
+
+    pub fn contains(&self, (row, column): Point) -> bool {
+        row < self.height && column < self.width
+    }
+
+    pub fn get(&self, point: Point) -> Option<&T> {
+        self.contains(point).then(|| &self.cells[point.0 * self.width + point.1])
+    }
+
+    pub fn get_mut(&mut self, point: Point) -> Option<&mut T> {
+        if self.contains(point) {
+            Some(&mut self.cells[point.0 * self.width + point.1])
+        } else {
+            None
+        }
+    }
+
+    /// the point `step` away from `point`, if it is inside the grid
+    pub fn offset(&self, (row, column): Point, (row_step, column_step): Step) -> Option<Point> {
+        let point = (row.checked_add_signed(row_step)?, column.checked_add_signed(column_step)?);
+        self.contains(point).then_some(point)
+    }
+
+    /// every point in row-major order
+    pub fn points(&self) -> impl Iterator<Item = Point> + '_ {
+        (0..self.height).flat_map(move |row| (0..self.width).map(move |column| (row, column)))
+    }
+
+    /// every cell with its point, in row-major order
+    pub fn iter(&self) -> impl Iterator<Item = (Point, &T)> + '_ {
+        self.points().zip(self.cells.iter())
+    }
+
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = (Point, &mut T)> + '_ {
+        let width = self.width.max(1);
+        self.cells.iter_mut().enumerate().map(move |(i, cell)| ((i / width, i % width), cell))
+    }
+
+    pub fn map<U>(&self, f: impl FnMut(&T) -> U) -> Grid<U> {
+        Grid { cells: self.cells.iter().map(f).collect(), width: self.width, height: self.height }
+    }
+
+    /// the points from `start` in steps of `step` until the edge of the grid; empty when `start` is outside
+    /// or `step` is (0, 0)
+    pub fn line(&self, start: Point, step: Step) -> Line<'_, T> {
+        let remaining = if !self.contains(start) || step == (0, 0) {
+            0
+        } else {
+            let limit = |position: usize, step: isize, size: usize| match step {
+                0 => usize::MAX,
+                step if step > 0 => (size - 1 - position) / step as usize + 1,
+                step => position / step.unsigned_abs() + 1,
+            };
+            limit(start.0, step.0, self.height).min(limit(start.1, step.1, self.width))
+        };
+        Line { grid: self, start, step, front: 0, back: remaining }
+    }
+
+    /// row `row` from left to right; `.rev()` walks it right to left
+    pub fn row(&self, row: usize) -> Line<'_, T> {
+        self.line((row, 0), EAST)
+    }
+
+    /// column `column` from top to bottom; `.rev()` walks it bottom to top
+    pub fn column(&self, column: usize) -> Line<'_, T> {
+        self.line((0, column), SOUTH)
+    }
+
+    /// the diagonal going down and to the right from `start`
+    pub fn diagonal(&self, start: Point) -> Line<'_, T> {
+        self.line(start, SOUTH_EAST)
+    }
+
+    /// the diagonal going down and to the left from `start`
+    pub fn anti_diagonal(&self, start: Point) -> Line<'_, T> {
+        self.line(start, SOUTH_WEST)
+    }
+
+    /// every down-right diagonal, starting from the bottom left corner and ending at the top right one
+    pub fn diagonals(&self) -> impl Iterator<Item = Line<'_, T>> + '_ {
+        let left = (0..self.height).rev().map(|row| (row, 0));
+        let top = (1..self.width).map(|column| (0, column));
+        left.chain(top).map(move |start| self.diagonal(start))
+    }
+
+    /// every down-left diagonal, starting from the top left corner and ending at the bottom right one;
+    /// a grid without columns has none
+    pub fn anti_diagonals(&self) -> impl Iterator<Item = Line<'_, T>> + '_ {
+        let top = (0..self.width).map(|column| (0, column));
+        let rows = if self.width == 0 { 0 } else { self.height };
+        let right = (1..rows).map(move |row| (row, self.width - 1));
+        top.chain(right).map(move |start| self.anti_diagonal(start))
+    }
+
+    /// the up to four points sharing an edge with `point`
+    pub fn neighbours4(&self, point: Point) -> impl Iterator<Item = Point> + '_ {
+        [NORTH, EAST, SOUTH, WEST].into_iter().filter_map(move |step| self.offset(point, step))
+    }
+
+    /// the up to eight points sharing an edge or a corner with `point`
+    pub fn neighbours8(&self, point: Point) -> impl Iterator<Item = Point> + '_ {
+        [NORTH, NORTH_EAST, EAST, SOUTH_EAST, SOUTH, SOUTH_WEST, WEST, NORTH_WEST]
+            .into_iter()
+            .filter_map(move |step| self.offset(point, step))
+    }
+}
+
+impl<T: Clone> Grid<T> {
+    pub fn filled(width: usize, height: usize, value: T) -> Grid<T> {
+        Grid { cells: vec![value; width * height], width, height }
+    }
+}
+
+impl<T> Index<Point> for Grid<T> {
+    type Output = T;
+
+    fn index(&self, point: Point) -> &T {
+        self.get(point).unwrap_or_else(|| panic!("{:?} is outside the {}x{} grid", point, self.width, self.height))
+    }
+}
+
+impl<T> IndexMut<Point> for Grid<T> {
+    fn index_mut(&mut self, point: Point) -> &mut T {
+        let (width, height) = (self.width, self.height);
+        self.get_mut(point).unwrap_or_else(|| panic!("{:?} is outside the {}x{} grid", point, width, height))
+    }
+}
+
+impl<T: fmt::Debug> fmt::Debug for Grid<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let rows: Vec<&[T]> = self.cells.chunks(self.width.max(1)).collect();
+        f.debug_struct("Grid").field("width", &self.width).field("height", &self.height).field("rows", &rows).finish()
+    }
+}
+
+/// Parses one digit per cell and one row per line, e.g. a tree height map
+impl FromStr for Grid<u8> {
+    type Err = io::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut cells = Vec::with_capacity(s.len());
+        let mut width = None;
+        let mut height = 0;
+        for (row, line) in s.lines().enumerate() {
+            let before = cells.len();
+            for (column, c) in line.chars().enumerate() {
+                let digit = c.to_digit(10).ok_or_else(|| {
+                    io::Error::new(ErrorKind::InvalidData, format!("{}:{}: {:?} is not a digit", row + 1, column + 1, c))
+                })?;
+                cells.push(digit as u8);
+            }
+            let length = cells.len() - before;
+            if *width.get_or_insert(length) != length {
+                let message = format!("row {} has {} cells, the first row has {}", row + 1, length, width.unwrap_or(0));
+                return Err(io::Error::new(ErrorKind::InvalidData, message));
+            }
+            height += 1;
+        }
+        let width = width.ok_or_else(|| io::Error::new(ErrorKind::InvalidData, "must have at least one row in data format"))?;
+        Grid::from_cells(width, height, cells)
+    }
+}
+
+/// The cells along a straight line through a grid, walkable from either end
+pub struct Line<'a, T> {
+    grid: &'a Grid<T>,
+    start: Point,
+    step: Step,
+    front: usize,
+    back: usize,
+}
+
+// derived Clone would needlessly require T: Clone
+impl<T> Clone for Line<'_, T> {
+    fn clone(&self) -> Self {
+        Line { grid: self.grid, start: self.start, step: self.step, front: self.front, back: self.back }
+    }
+}
+
+impl<'a, T> Line<'a, T> {
+    fn point(&self, i: usize) -> Point {
+        let i = i as isize;
+        let row = self.start.0 as isize + self.step.0 * i;
+        let column = self.start.1 as isize + self.step.1 * i;
+        (row as usize, column as usize)
+    }
+}
+
+impl<'a, T> Iterator for Line<'a, T> {
+    type Item = (Point, &'a T);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.front >= self.back {
+            return None;
+        }
+        let point = self.point(self.front);
+        self.front += 1;
+        let grid = self.grid;
+        Some((point, &grid[point]))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let remaining = self.back - self.front;
+        (remaining, Some(remaining))
+    }
+}
+
+impl<T> DoubleEndedIterator for Line<'_, T> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        if self.front >= self.back {
+            return None;
+        }
+        self.back -= 1;
+        let point = self.point(self.back);
+        let grid = self.grid;
+        Some((point, &grid[point]))
+    }
+}
+
+impl<T> ExactSizeIterator for Line<'_, T> {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn grid() -> Grid<u8> {
+        "123\n456".parse().unwrap()
+    }
+
+    fn values<'a>(line: impl Iterator<Item = (Point, &'a u8)>) -> Vec<u8> {
+        line.map(|(_, value)| *value).collect()
+    }
+
+    #[test]
+    fn rows_and_columns_in_both_directions() {
+        let grid = grid();
+        assert_eq!((grid.width(), grid.height()), (3, 2));
+        assert_eq!(values(grid.row(1)), [4, 5, 6]);
+        assert_eq!(values(grid.row(1).rev()), [6, 5, 4]);
+        assert_eq!(values(grid.column(2)), [3, 6]);
+        assert_eq!(values(grid.column(2).rev()), [6, 3]);
+        assert_eq!(grid.column(0).len(), 2);
+        assert_eq!(grid.row(2).count(), 0);
+    }
+
+    #[test]
+    fn lines_stop_at_the_edge() {
+        let grid = grid();
+        assert_eq!(grid.line((1, 0), NORTH_EAST).map(|(point, _)| point).collect::<Vec<_>>(), [(1, 0), (0, 1)]);
+        assert_eq!(values(grid.line((0, 0), (1, 2))), [1, 6]);
+        assert_eq!(values(grid.line((0, 2), (0, -2))), [3, 1]);
+        assert_eq!(grid.line((0, 0), (0, 0)).count(), 0);
+        assert_eq!(grid.line((5, 5), EAST).count(), 0);
+        let mut line = grid.line((0, 0), EAST);
+        assert_eq!((line.next().map(|(_, value)| *value), line.next_back().map(|(_, value)| *value)), (Some(1), Some(3)));
+        assert_eq!(values(line), [2]);
+    }
+
+    #[test]
+    fn diagonals_cover_every_cell_once() {
+        let grid = grid();
+        let diagonals: Vec<Vec<u8>> = grid.diagonals().map(values).collect();
+        assert_eq!(diagonals, [vec![4], vec![1, 5], vec![2, 6], vec![3]]);
+        let anti_diagonals: Vec<Vec<u8>> = grid.anti_diagonals().map(values).collect();
+        assert_eq!(anti_diagonals, [vec![1], vec![2, 4], vec![3, 5], vec![6]]);
+    }
+
+    #[test]
+    fn grids_without_columns() {
+        let grid: Grid<u8> = "\n\n".parse().unwrap();
+        assert_eq!((grid.width(), grid.height()), (0, 2));
+        assert_eq!(grid.anti_diagonals().count(), 0);
+        assert_eq!(grid.diagonals().map(|line| line.count()).sum::<usize>(), 0);
+        assert_eq!(grid.points().count(), 0);
+    }
+
+    #[test]
+    fn neighbours() {
+        let grid = grid();
+        let mut corner: Vec<Point> = grid.neighbours4((0, 0)).collect();
+        corner.sort();
+        assert_eq!(corner, [(0, 1), (1, 0)]);
+        let mut middle: Vec<Point> = grid.neighbours8((1, 1)).collect();
+        middle.sort();
+        assert_eq!(middle, [(0, 0), (0, 1), (0, 2), (1, 0), (1, 2)]);
+        assert_eq!(grid.neighbours4((0, 1)).count(), 3);
+    }
+
+    #[test]
+    fn parse_errors() {
+        let error = "12\n3a".parse::<Grid<u8>>().unwrap_err();
+        assert_eq!(error.kind(), ErrorKind::InvalidData);
+        assert!(error.to_string().contains("2:2"), "{}", error);
+        let error = "12\n345".parse::<Grid<u8>>().unwrap_err();
+        assert!(error.to_string().contains("row 2 has 3 cells, the first row has 2"), "{}", error);
+        assert!("".parse::<Grid<u8>>().is_err());
+        assert!(Grid::from_cells(2, 2, vec![1, 2, 3]).is_err());
+    }
+}
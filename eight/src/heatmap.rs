@@ -0,0 +1,100 @@
+use std::io;
+use std::io::{ErrorKind, Write};
+use crate::forest::{top_locations, TieBreak};
+use crate::grid::{Grid, Point};
+
+pub type Rgb = [u8; 3];
+
+/// the viridis colour map from low to high, interpolated linearly between these stops
+const GRADIENT: [Rgb; 5] = [[68, 1, 84], [59, 82, 139], [33, 145, 140], [94, 201, 98], [253, 231, 37]];
+/// the best location, outside the gradient so it stands out
+const BEST: Rgb = [230, 25, 25];
+/// how much of its colour a tree hidden from the edges keeps
+const HIDDEN_BRIGHTNESS: f64 = 0.45;
+
+fn gradient(fraction: f64) -> Rgb {
+    let position = fraction.clamp(0.0, 1.0) * (GRADIENT.len() - 1) as f64;
+    let index = (position.floor() as usize).min(GRADIENT.len() - 2);
+    let t = position - index as f64;
+    let (low, high) = (GRADIENT[index], GRADIENT[index + 1]);
+    [0, 1, 2].map(|channel| (low[channel] as f64 + (high[channel] as f64 - low[channel] as f64) * t).round() as u8)
+}
+
+/// One colour per tree: the scenic score on a log scale, dimmed for trees hidden from the edges, with
+/// the best location in red
+pub fn colors(scores: &Grid<usize>, visible: &Grid<bool>) -> Grid<Rgb> {
+    colors_with_best(scores, visible, best_location(scores))
+}
+
+fn best_location(scores: &Grid<usize>) -> Option<Point> {
+    top_locations(scores, 1, TieBreak::ReadingOrder).first().copied()
+}
+
+fn colors_with_best(scores: &Grid<usize>, visible: &Grid<bool>, best: Option<Point>) -> Grid<Rgb> {
+    let max = scores.iter().map(|(_, score)| *score).max().unwrap_or(0);
+    let scale = (max as f64).ln_1p();
+    let mut colors = scores.map(|score| gradient(if scale > 0.0 { (*score as f64).ln_1p() / scale } else { 0.0 }));
+    for (point, color) in colors.iter_mut() {
+        if Some(point) == best {
+            *color = BEST;
+        } else if !visible[point] {
+            *color = color.map(|channel| (channel as f64 * HIDDEN_BRIGHTNESS) as u8);
+        }
+    }
+    colors
+}
+
+/// Draws the heights on a 24-bit ANSI background of their scenic score: visible trees are bold, hidden
+/// ones dim, and the best location is an `@`
+pub fn write_ansi<W: Write>(heights: &Grid<u8>, scores: &Grid<usize>, visible: &Grid<bool>, output: &mut W) -> io::Result<()> {
+    let best = best_location(scores);
+    let colors = colors_with_best(scores, visible, best);
+    for row in 0..heights.height() {
+        for (point, height) in heights.row(row) {
+            let [r, g, b] = colors[point];
+            let style = if visible[point] { "1;97" } else { "2;37" };
+            let symbol = if Some(point) == best { '@' } else { char::from(b'0' + height % 10) };
+            write!(output, "\x1b[{};48;2;{};{};{}m{} ", style, r, g, b, symbol)?;
+        }
+        writeln!(output, "\x1b[0m")?;
+    }
+    let max = best.map_or(0, |best| scores[best]);
+    writeln!(output, "bold: visible from an edge, dim: hidden, @: best location {:?} scoring {}", best, max)
+}
+
+/// each pixel of `image` as a `scale` x `scale` square, row by row
+fn scaled_rows(image: &Grid<Rgb>, scale: usize) -> impl Iterator<Item = Vec<u8>> + '_ {
+    (0..image.height()).flat_map(move |row| {
+        let pixels: Vec<u8> = image.row(row).flat_map(|(_, color)| color.repeat(scale)).collect();
+        std::iter::repeat_n(pixels, scale)
+    })
+}
+
+/// Writes a binary PPM (P6), which needs no compression and opens in most image viewers
+pub fn write_ppm<W: Write>(image: &Grid<Rgb>, scale: usize, output: &mut W) -> io::Result<()> {
+    write!(output, "P6\n{} {}\n255\n", image.width() * scale, image.height() * scale)?;
+    for row in scaled_rows(image, scale) {
+        output.write_all(&row)?;
+    }
+    output.flush()
+}
+
+/// Writes an 8-bit RGB PNG one row at a time, so large forests never hold the whole image in memory
+pub fn write_png<W: Write>(image: &Grid<Rgb>, scale: usize, output: &mut W) -> io::Result<()> {
+    let dimension = |cells: usize| {
+        cells.checked_mul(scale).and_then(|pixels| u32::try_from(pixels).ok()).ok_or_else(|| {
+            let message = format!("a {}x{} forest at scale {} is too large for a PNG", image.width(), image.height(), scale);
+            io::Error::new(ErrorKind::InvalidInput, message)
+        })
+    };
+    let (width, height) = (dimension(image.width())?, dimension(image.height())?);
+    let mut encoder = png::Encoder::new(output, width, height);
+    encoder.set_color(png::ColorType::Rgb);
+    encoder.set_depth(png::BitDepth::Eight);
+    let mut writer = encoder.write_header().map_err(io::Error::other)?;
+    let mut stream = writer.stream_writer().map_err(io::Error::other)?;
+    for row in scaled_rows(image, scale) {
+        stream.write_all(&row)?;
+    }
+    stream.finish().map_err(io::Error::other)
+}
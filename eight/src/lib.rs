@@ -0,0 +1,5 @@
+pub mod forest;
+pub mod grid;
+pub mod heatmap;
+pub mod parallel;
+pub mod viewshed;
@@ -1,215 +1,321 @@
-use std::collections::HashSet;
 use std::error::Error;
-use std::fs::File;
-use std::io::{BufRead, BufReader};
-use std::slice::Iter;
+use std::fs;
+use std::io;
+use std::io::{IsTerminal, Write};
+use std::time::Instant;
+use tracing::{info, info_span, Level};
+use eight::forest;
+use eight::parallel;
+use eight::heatmap;
+use eight::viewshed;
+use eight::viewshed::Observer;
+use serde_json::json;
+use eight::grid::{Grid, Step};
 
-type Tree = u64;
-
-type Point = (usize, usize);
-
-#[derive(Debug)]
-struct Grid {
-    entries: Vec<Vec<Tree>>,
-    width: usize,
-    height: usize,
+/// `-q` silences logging, each `-v` (or `-vv`, `-vvv`) raises it from warnings to info, debug and trace
+fn log_level(args: &[String]) -> Option<Level> {
+    if args.iter().any(|arg| arg == "-q" || arg == "--quiet") {
+        return None;
+    }
+    let verbosity: usize = args
+        .iter()
+        .filter(|arg| arg.starts_with("-v") && arg[1..].chars().all(|c| c == 'v'))
+        .map(|arg| arg.len() - 1)
+        .sum::<usize>()
+        + args.iter().filter(|arg| *arg == "--verbose").count();
+    Some(match verbosity {
+        0 => Level::WARN,
+        1 => Level::INFO,
+        2 => Level::DEBUG,
+        _ => Level::TRACE,
+    })
 }
 
-struct Column<'a> {
-    column_index: usize,
-    row_index: usize,
-    grid: &'a Grid,
+fn is_log_flag(arg: &str) -> bool {
+    matches!(arg, "-q" | "--quiet" | "--verbose") || (arg.starts_with("-v") && arg[1..].chars().all(|c| c == 'v'))
 }
 
-impl<'a> Iterator for Column<'a> {
-    type Item = (Point, Tree);
-
-    fn next(&mut self) -> Option<Self::Item> {
-        if self.row_index >= self.grid.height {
-            return None;
+/// Takes out the logging flags and `--threads N`, which may appear anywhere, so the subcommand is the
+/// first of the remaining arguments
+fn split_global_flags(args: &[String]) -> Result<(Vec<String>, Option<usize>), Box<dyn Error>> {
+    let mut rest = Vec::new();
+    let mut threads = None;
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        if arg == "--threads" {
+            threads = Some(args.next().ok_or("--threads needs a number")?.parse()?);
+        } else if !is_log_flag(arg) {
+            rest.push(arg.clone());
         }
-        let element = self.grid.entries[self.row_index][self.column_index];
-        let return_value = Some(((self.row_index, self.column_index), element));
-        self.row_index += 1;
-        return_value
     }
+    Ok((rest, threads))
 }
 
-struct Row<'a> {
-    column_index: usize,
-    row_index: usize,
-    grid: &'a Grid,
+fn flag_value<'a>(args: &'a [String], flag: &str) -> Option<&'a str> {
+    let position = args.iter().position(|arg| arg == flag)?;
+    args.get(position + 1).map(|value| value.as_str())
 }
 
-impl<'a> Iterator for Row<'a> {
-    type Item = (Point, Tree);
-
-    fn next(&mut self) -> Option<Self::Item> {
-        if self.column_index >= self.grid.width {
-            return None;
-        }
-        let element = self.grid.entries[self.row_index][self.column_index];
-        let return_value = Some(((self.row_index, self.column_index), element));
-        self.column_index += 1;
-        return_value
+/// `orthogonal`, `diagonal`, `compass`, or steps like `1,2;-1,0` separated by semicolons
+fn parse_directions(directions: &str) -> Result<Vec<Step>, Box<dyn Error>> {
+    match directions {
+        "orthogonal" => Ok(forest::ORTHOGONAL.to_vec()),
+        "diagonal" => Ok(forest::DIAGONAL.to_vec()),
+        "compass" => Ok(forest::COMPASS.to_vec()),
+        steps => steps
+            .split(';')
+            .map(|step| {
+                let (rows, columns) = step.split_once(',').ok_or_else(|| format!("direction {:?} should look like 1,-1", step))?;
+                Ok((rows.trim().parse()?, columns.trim().parse()?))
+            })
+            .collect(),
     }
 }
 
-impl<'a> Grid {
-    fn row(&self, row_index: usize) -> impl Iterator<Item=(Point, Tree)> + '_ {
-        let row_iterator = Row {
-            column_index: 0,
-            grid: self,
-            row_index,
-        };
-        row_iterator.into_iter()
+/// `eight viewshed ROW COLUMN EYE_HEIGHT` draws what an observer at that tree sees: `@` is the observer,
+/// `#` a tree whose top is in sight and `.` a hidden one
+fn print_viewshed(grid: &Grid<u8>, args: &[String]) -> Result<(), Box<dyn Error>> {
+    let [row, column, eye_height] = args.get(..3).ok_or("viewshed needs ROW COLUMN EYE_HEIGHT")? else {
+        unreachable!("the slice has three elements")
+    };
+    let observer = Observer { position: (row.parse()?, column.parse()?), eye_height: eye_height.parse()? };
+    let viewshed = info_span!("viewshed").in_scope(|| viewshed::viewshed(grid, &observer))?;
+    for row in 0..grid.height() {
+        let line: String = viewshed
+            .map
+            .row(row)
+            .map(|(point, visible)| match (point == observer.position, visible) {
+                (true, _) => '@',
+                (false, true) => '#',
+                (false, false) => '.',
+            })
+            .collect();
+        println!("{}", line);
     }
+    println!("{} trees visible from {:?} at eye height {}", viewshed.visible.len(), observer.position, observer.eye_height);
+    Ok(())
+}
 
-    fn column(&self, column_index: usize) -> impl Iterator<Item=(Point, Tree)> + '_ {
-        let column_iterator = Column {
-            column_index,
-            grid: self,
-            row_index: 0,
-        };
-        column_iterator.into_iter()
+/// `eight heatmap [--image PATH.png|PATH.ppm] [--scale N]` draws scenic scores in the terminal, or as an
+/// image with each tree `--scale` pixels wide
+fn write_heatmap(grid: &Grid<u8>, scores: &Grid<usize>, visible: &Grid<bool>, args: &[String]) -> Result<(), Box<dyn Error>> {
+    let Some(path) = flag_value(args, "--image") else {
+        return Ok(heatmap::write_ansi(grid, scores, visible, &mut io::BufWriter::new(io::stdout().lock()))?);
+    };
+    let scale: usize = flag_value(args, "--scale").map_or(Ok(8), str::parse)?;
+    if scale == 0 {
+        return Err("--scale must be at least 1".into());
+    }
+    // checked before creating the file, so a bad argument doesn't leave a broken image behind
+    let ppm = path.ends_with(".ppm");
+    if !ppm && !path.ends_with(".png") {
+        return Err(format!("{}: the image must be a .png or a .ppm", path).into());
+    }
+    let image = heatmap::colors(scores, visible);
+    let mut output = io::BufWriter::new(fs::File::create(path)?);
+    if ppm {
+        heatmap::write_ppm(&image, scale, &mut output)?;
+    } else {
+        heatmap::write_png(&image, scale, &mut output)?;
     }
+    info!(path, scale, "wrote heatmap");
+    Ok(())
 }
 
-fn count_visible(visibility: &mut HashSet<(usize, usize)>, entries: Vec<(Point, Tree)>) {
-    println!("segment is : {:?}", entries);
-    let mut max_seen: i64 = -1;
-    for (point, entry) in entries.iter() {
-        if (*entry as i64) > max_seen {
-            println!("entry: {:?} greater than max_seen: {:?} at point: {:?}", entry, max_seen, point);
-            visibility.insert(*point);
-            max_seen = (*entry as i64);
-        }
+/// `eight best [--top K] [--tie-break reading|centre] [--json]` lists the best treehouse locations with
+/// their viewing distance in each direction
+fn print_best(grid: &Grid<u8>, analysis: &forest::Analysis, args: &[String]) -> Result<(), Box<dyn Error>> {
+    let k: usize = flag_value(args, "--top").map_or(Ok(1), str::parse)?;
+    let tie_break: forest::TieBreak = flag_value(args, "--tie-break").map_or(Ok(forest::TieBreak::ReadingOrder), str::parse)?;
+    let locations = analysis.best_locations(k, tie_break)?;
+    if args.iter().any(|arg| arg == "--json") {
+        let locations: Vec<serde_json::Value> = locations
+            .iter()
+            .enumerate()
+            .map(|(i, location)| {
+                // an array in analysis order, since a repeated step would collapse into one object key
+                let distances: Vec<serde_json::Value> = location
+                    .distances
+                    .iter()
+                    .map(|(direction, distance)| {
+                        json!({ "direction": forest::direction_name(*direction), "step": [direction.0, direction.1], "distance": distance })
+                    })
+                    .collect();
+                json!({
+                    "rank": i + 1,
+                    "row": location.position.0,
+                    "column": location.position.1,
+                    "height": grid[location.position],
+                    "score": location.score,
+                    "distances": distances,
+                })
+            })
+            .collect();
+        println!("{}", serde_json::to_string_pretty(&locations)?);
+        return Ok(());
     }
-    max_seen = -1;
-
-    for (point, entry) in entries.iter().rev() {
-        if (*entry as i64) > max_seen {
-            println!("entry: {:?} greater than max_seen: {:?} at point: {:?} (backwards)", entry, max_seen, point);
-            visibility.insert(*point);
-            max_seen = (*entry as i64);
-        }
+    for (i, location) in locations.iter().enumerate() {
+        let distances: Vec<String> = location
+            .distances
+            .iter()
+            .map(|(direction, distance)| format!("{} {}", forest::direction_name(*direction), distance))
+            .collect();
+        println!(
+            "{:>3}. {:?} height {} score {}: {}",
+            i + 1,
+            location.position,
+            grid[location.position],
+            location.score,
+            distances.join(", ")
+        );
     }
+    Ok(())
 }
 
-fn scenic_score( scores: &mut Vec<Vec<usize>>, entries: Vec<(Point, Tree)>)  {
-    let mut sightlines: Vec<(u64, usize)> = Vec::new();
-    for (point, entry) in entries.iter() {
-        let mut new_count = 1;
-        let (i, j) = point;
-
-        let mut individual_score = 0;
-        println!("sightlines: {:?}", sightlines);
-        loop {
-            if let Some((previous_height, count)) = sightlines.pop() {
-                if *entry > previous_height {
-
-                    individual_score += count;
-                    new_count += count;
-                } else {
-                    individual_score += 1;
-                    sightlines.push((previous_height, count));
-                    sightlines.push((*entry, new_count));
-                    break
-                }
-            } else {
-                sightlines.push((*entry, new_count));
-                break
-            }
-        }
+/// an N x N forest of heights 0 to 9, each mixed from its position and `seed` like splitmix64
+fn generated_forest(size: usize, seed: u64) -> Grid<u8> {
+    Grid::from_fn(size, size, |(row, column)| {
+        let mut x = seed ^ ((row as u64) << 32 | column as u64);
+        x = (x ^ (x >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        x = (x ^ (x >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        ((x ^ (x >> 31)) % 10) as u8
+    })
+}
 
-        println!("individual score: {}", individual_score);
-        scores[*i][*j] *= individual_score;
-    }
-    let mut sightlines: Vec<(u64, usize)> = Vec::new();
-
-    println!("reverse: ");
-    for (point, entry) in entries.iter().rev() {
-        let mut new_count = 1;
-        let (i, j) = point;
-
-        let mut individual_score = 0;
-        println!("sightlines: {:?}", sightlines);
-        loop {
-            if let Some((previous_height, count)) = sightlines.pop() {
-                if *entry > previous_height {
-
-                    individual_score += count;
-                    new_count += count;
-                } else {
-                    individual_score += 1;
-                    sightlines.push((previous_height, count));
-                    sightlines.push((*entry, new_count));
-                    break
-                }
-            } else {
-                sightlines.push((*entry, new_count));
-                break
-            }
-        }
+/// `eight bench [--size N] [--seed S] [--compare]` times the parallel passes on a generated N x N
+/// forest; `--compare` also runs the sequential analysis, which needs far more memory, and checks
+/// that both agree
+fn bench(args: &[String]) -> Result<(), Box<dyn Error>> {
+    let size: usize = flag_value(args, "--size").map_or(Ok(10_000), str::parse)?;
+    let seed: u64 = flag_value(args, "--seed").map_or(Ok(0x5EED), str::parse)?;
+    let grid = info_span!("generate", size).in_scope(|| generated_forest(size, seed));
+
+    let start = Instant::now();
+    let analysis = info_span!("parallel").in_scope(|| parallel::analyse(&grid))?;
+    let visible = analysis.visible.count();
+    let max_score = analysis.max_score();
+    println!("parallel ({} threads): {} visible, max scenic score {}, {:.2?}", rayon::current_num_threads(), visible, max_score, start.elapsed());
 
-        println!("individual score: {}", individual_score);
-        scores[*i][*j] *= individual_score;
+    if args.iter().any(|arg| arg == "--compare") {
+        let start = Instant::now();
+        let sequential = info_span!("sequential").in_scope(|| forest::analyse(&grid, &forest::ORTHOGONAL))?;
+        let sequential_visible = sequential.visible_from_any();
+        let sequential_scores = sequential.scenic_scores()?;
+        println!(
+            "sequential: {} visible, max scenic score {}, {:.2?}",
+            sequential_visible.iter().filter(|(_, visible)| **visible).count(),
+            sequential_scores.iter().map(|(_, score)| *score).max().unwrap_or(0),
+            start.elapsed()
+        );
+        let disagreement = grid.points().find(|point| {
+            analysis.visible.get(*point) != sequential_visible[*point] || analysis.scores[*point] != sequential_scores[*point] as u64
+        });
+        if let Some(point) = disagreement {
+            return Err(format!("the parallel and sequential analyses disagree at {:?}", point).into());
+        }
     }
+    Ok(())
+}
 
+fn parse(path: &str) -> Result<Grid<u8>, Box<dyn Error>> {
+    let _span = info_span!("parse", path).entered();
+    let grid: Grid<u8> = fs::read_to_string(path)?.parse()?;
+    info!(width = grid.width(), height = grid.height(), "parsed grid");
+    Ok(grid)
+}
 
+/// Writes the grid, the visible trees and the score heatmap, the views that used to be printed on every run
+fn dump<W: Write>(grid: &Grid<u8>, visible: &Grid<bool>, scores: &Grid<usize>, output: &mut W) -> io::Result<()> {
+    writeln!(output, "grid ({}x{}):", grid.width(), grid.height())?;
+    for row in 0..grid.height() {
+        writeln!(output, "{}", grid.row(row).map(|(_, tree)| tree.to_string()).collect::<String>())?;
+    }
+    writeln!(output, "visible trees:")?;
+    for row in 0..visible.height() {
+        writeln!(output, "{}", visible.row(row).map(|(_, visible)| if *visible { '#' } else { '.' }).collect::<String>())?;
+    }
+    writeln!(output, "scenic scores:")?;
+    heatmap::write_ansi(grid, scores, visible, output)?;
+    Ok(())
 }
 
+/// the default summary takes `--dump`, `--parallel` and `--directions D`; anything else, such as a
+/// subcommand after a flag, is a mistake rather than something to ignore
+fn check_summary_flags(args: &[String]) -> Result<(), Box<dyn Error>> {
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--dump" | "--parallel" => {}
+            "--directions" => {
+                args.next().ok_or("--directions needs a value")?;
+            }
+            other => return Err(format!("unexpected argument {:?}", other).into()),
+        }
+    }
+    Ok(())
+}
 
 fn main() -> Result<(), Box<dyn Error>> {
-    let file = File::open("./eight/input.txt")?;
-    let reader = BufReader::new(file);
-    let mut rows = Vec::new();
-    for line in reader.lines() {
-        let line = line?;
-        let mut row = Vec::new();
-        for c in line.chars() {
-            let num: u64 = c.to_digit(10).expect("failed to parse char as digit") as u64;
-            row.push(num)
-        }
-        rows.push(row);
-    }
-    let height = rows.len();
-    let width = rows.first().expect("must have at least one row in data format").len();
-    let grid = Grid {
-        entries: rows,
-        height,
-        width,
-    };
-    println!("grid: {:?}", grid);
-    let mut visibility: HashSet<(usize, usize)> = HashSet::new();
-    let mut scores = Vec::new();
-    for i in 0..grid.height {
-        if i == 0  || i == grid.height - 1{
-            scores.push(vec![0; grid.width])
-        } else {
-            let mut row = vec![1; grid.width];
-            row[0] = 0;
-            row[grid.width - 1] = 0;
-            scores.push(row)
-        }
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if let Some(level) = log_level(&args) {
+        tracing_subscriber::fmt()
+            .with_max_level(level)
+            .with_writer(io::stderr)
+            .with_ansi(io::stderr().is_terminal())
+            .without_time()
+            .init();
+    }
+    let (args, threads) = split_global_flags(&args)?;
+    if let Some(threads) = threads {
+        rayon::ThreadPoolBuilder::new().num_threads(threads).build_global()?;
     }
-    println!("scores: {:?}", scores);
-    println!("rows:");
-    for row_index in 0..grid.height {
-        println!("row: {}", row_index);
-        count_visible(&mut visibility, grid.row(row_index).collect());
-        scenic_score(&mut scores, grid.row(row_index).collect());
+    let command = args.first().map(|arg| arg.as_str()).filter(|arg| !arg.starts_with('-'));
+    if command == Some("bench") {
+        return bench(&args[1..]);
+    }
+    let grid = parse("./eight/input.txt")?;
+    match command {
+        Some("viewshed") => return print_viewshed(&grid, &args[1..]),
+        Some("best" | "heatmap") => {}
+        Some(other) => return Err(format!("unknown subcommand {:?}", other).into()),
+        None => check_summary_flags(&args)?,
+    }
+    if command.is_none() && args.iter().any(|arg| arg == "--parallel") {
+        let analysis = info_span!("parallel").in_scope(|| parallel::analyse(&grid))?;
+        println!("visible trees: {}", analysis.visible.count());
+        println!("max scenic score: {}", analysis.max_score());
+        return Ok(());
     }
 
-    println!("columns:");
-    for col_index in 0..grid.width {
-        println!("column: {}", col_index);
-        count_visible(&mut visibility, grid.column(col_index).collect());
-        scenic_score(&mut scores, grid.column(col_index).collect());
+    let directions = match flag_value(&args, "--directions") {
+        Some(directions) => parse_directions(directions)?,
+        None => forest::ORTHOGONAL.to_vec(),
+    };
+    let analysis = info_span!("analysis").in_scope(|| forest::analyse(&grid, &directions))?;
+    let visible = analysis.visible_from_any();
+    let visible_count = visible.iter().filter(|(_, visible)| **visible).count();
+    info!(visible = visible_count, "counted visible trees");
+
+    let scores = analysis.scenic_scores()?;
+    let max_score = scores.iter().map(|(_, score)| *score).max().unwrap_or(0);
+    info!(max_score, "scored trees");
+
+    match command {
+        Some("best") => return print_best(&grid, &analysis, &args[1..]),
+        Some("heatmap") => return write_heatmap(&grid, &scores, &visible, &args[1..]),
+        _ => {}
+    }
+    if args.iter().any(|arg| arg == "--dump") {
+        dump(&grid, &visible, &scores, &mut io::stderr().lock())?;
+    }
+    println!("visible trees: {}", visible_count);
+    println!("max scenic score: {}", max_score);
+    if flag_value(&args, "--directions").is_some() {
+        for (i, direction) in analysis.directions.iter().enumerate() {
+            let visible = analysis.visible[i].iter().filter(|(_, visible)| **visible).count();
+            let longest = analysis.distances[i].iter().map(|(_, distance)| *distance).max().unwrap_or(0);
+            println!("{:>8}: {} visible, longest view {}", forest::direction_name(*direction), visible, longest);
+        }
     }
-    let max_score = scores.iter().map(|v| v.iter().max()).max();
-    println!("sum is {}", visibility.len());
-    println!("scores: {:?}", scores);
-    println!("max score is: {:?}", max_score  );
     Ok(())
 }
@@ -0,0 +1,252 @@
+use std::io;
+use std::io::ErrorKind;
+use rayon::prelude::*;
+use tracing::debug;
+use crate::grid::{Grid, Point};
+
+/// One bit per cell, row by row
+#[derive(Clone, PartialEq, Eq)]
+pub struct Bitmap {
+    words: Vec<u64>,
+    width: usize,
+    height: usize,
+}
+
+impl Bitmap {
+    pub fn new(width: usize, height: usize) -> Bitmap {
+        Bitmap { words: vec![0; (width * height).div_ceil(64)], width, height }
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    pub fn get(&self, (row, column): Point) -> bool {
+        let bit = row * self.width + column;
+        row < self.height && column < self.width && self.words[bit / 64] & (1 << (bit % 64)) != 0
+    }
+
+    pub fn set(&mut self, (row, column): Point) {
+        let bit = row * self.width + column;
+        self.words[bit / 64] |= 1 << (bit % 64);
+    }
+
+    pub fn count(&self) -> usize {
+        self.words.iter().map(|word| word.count_ones() as usize).sum()
+    }
+
+    /// every set point in row-major order
+    pub fn points(&self) -> impl Iterator<Item = Point> + '_ {
+        self.words.iter().enumerate().flat_map(move |(i, word)| {
+            let mut word = *word;
+            std::iter::from_fn(move || {
+                (word != 0).then(|| {
+                    let bit = i * 64 + word.trailing_zeros() as usize;
+                    word &= word - 1;
+                    (bit / self.width, bit % self.width)
+                })
+            })
+        })
+    }
+}
+
+/// Visible trees and scenic scores of a forest, computed with independent passes in parallel
+pub struct ParallelAnalysis {
+    pub visible: Bitmap,
+    pub scores: Grid<u64>,
+}
+
+impl ParallelAnalysis {
+    pub fn max_score(&self) -> u64 {
+        self.scores.cells().iter().copied().max().unwrap_or(0)
+    }
+}
+
+/// Adds the next tree of a line to its stack of (height, trees hidden behind it) and returns whether it
+/// is taller than every tree before it, and how many trees it sees looking back along the line
+fn step<'a, T: Ord>(sightlines: &mut Vec<(&'a T, u32)>, tree: &'a T) -> (bool, u32) {
+    let mut distance = 0;
+    let mut hidden = 1;
+    while let Some(&(previous, count)) = sightlines.last() {
+        if tree > previous {
+            sightlines.pop();
+            distance += count;
+            hidden += count;
+        } else {
+            distance += 1;
+            break;
+        }
+    }
+    let visible = sightlines.is_empty();
+    sightlines.push((tree, hidden));
+    (visible, distance)
+}
+
+/// what one thread found for a band of columns: the visible trees and the product of the up and down
+/// viewing distances, both for the band's cells only, row by row
+struct ColumnBand {
+    start: usize,
+    width: usize,
+    visible: Bitmap,
+    products: Vec<u32>,
+}
+
+fn column_band<T: Ord>(grid: &Grid<T>, start: usize, width: usize) -> ColumnBand {
+    let (cells, grid_width, height) = (grid.cells(), grid.width(), grid.height());
+    let mut visible = Bitmap::new(width, height);
+    let mut products = vec![0; width * height];
+    let mut sightlines: Vec<Vec<(&T, u32)>> = vec![Vec::new(); width];
+    for row in 0..height {
+        for (j, stack) in sightlines.iter_mut().enumerate() {
+            let (seen, distance) = step(stack, &cells[row * grid_width + start + j]);
+            if seen {
+                visible.set((row, j));
+            }
+            products[row * width + j] = distance;
+        }
+    }
+    sightlines.iter_mut().for_each(Vec::clear);
+    for row in (0..height).rev() {
+        for (j, stack) in sightlines.iter_mut().enumerate() {
+            let (seen, distance) = step(stack, &cells[row * grid_width + start + j]);
+            if seen {
+                visible.set((row, j));
+            }
+            products[row * width + j] *= distance;
+        }
+    }
+    ColumnBand { start, width, visible, products }
+}
+
+/// Computes the same visibility and scenic scores as `forest::visible_trees` and `forest::scenic_scores`.
+/// Bands of rows and bands of columns go to rayon's threads; each thread writes its own bitmap and
+/// score buffer, and the buffers are merged once every band is done.
+pub fn analyse<T: Ord + Sync>(grid: &Grid<T>) -> io::Result<ParallelAnalysis> {
+    let (width, height) = (grid.width(), grid.height());
+    // the product of the up and down distances is below (height / 2)², which must fit the u32 buffers
+    if height > 1 << 17 {
+        return Err(io::Error::new(ErrorKind::InvalidInput, "forests taller than 131072 rows aren't supported"));
+    }
+    let bands = rayon::current_num_threads() * 4;
+    let mut scores = vec![0u64; width * height];
+    let mut visible = Bitmap::new(width, height);
+    if width == 0 || height == 0 {
+        return Ok(ParallelAnalysis { visible, scores: Grid::from_cells(width, height, scores)? });
+    }
+
+    let band_rows = height.div_ceil(bands);
+    debug!(band_rows, "row passes");
+    let row_bands: Vec<Bitmap> = scores
+        .par_chunks_mut(width * band_rows)
+        .zip(grid.cells().par_chunks(width * band_rows))
+        .map(|(scores, trees)| {
+            let mut visible = Bitmap::new(width, trees.len() / width);
+            let mut sightlines = Vec::with_capacity(width);
+            for (row, (scores, trees)) in scores.chunks_mut(width).zip(trees.chunks(width)).enumerate() {
+                sightlines.clear();
+                for (column, tree) in trees.iter().enumerate() {
+                    let (seen, distance) = step(&mut sightlines, tree);
+                    if seen {
+                        visible.set((row, column));
+                    }
+                    scores[column] = distance as u64;
+                }
+                sightlines.clear();
+                for (column, tree) in trees.iter().enumerate().rev() {
+                    let (seen, distance) = step(&mut sightlines, tree);
+                    if seen {
+                        visible.set((row, column));
+                    }
+                    scores[column] *= distance as u64;
+                }
+            }
+            visible
+        })
+        .collect();
+
+    let band_columns = width.div_ceil(bands);
+    debug!(band_columns, "column passes");
+    let column_bands: Vec<ColumnBand> = (0..width)
+        .step_by(band_columns)
+        .collect::<Vec<_>>()
+        .into_par_iter()
+        .map(|start| column_band(grid, start, band_columns.min(width - start)))
+        .collect();
+
+    scores.par_chunks_mut(width).enumerate().for_each(|(row, scores)| {
+        for band in &column_bands {
+            let products = &band.products[row * band.width..(row + 1) * band.width];
+            for (score, product) in scores[band.start..band.start + band.width].iter_mut().zip(products) {
+                *score *= *product as u64;
+            }
+        }
+    });
+    for (i, band) in row_bands.iter().enumerate() {
+        band.points().for_each(|(row, column)| visible.set((i * band_rows + row, column)));
+    }
+    for band in &column_bands {
+        band.visible.points().for_each(|(row, column)| visible.set((row, band.start + column)));
+    }
+    Ok(ParallelAnalysis { visible, scores: Grid::from_cells(width, height, scores)? })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::forest;
+
+    /// a `width` x `height` forest of heights `0..=max_height` from an xorshift stream in `state`
+    fn random_forest(state: &mut u64, width: usize, height: usize, max_height: u64) -> Grid<u8> {
+        Grid::from_fn(width, height, |_| {
+            *state ^= *state << 13;
+            *state ^= *state >> 7;
+            *state ^= *state << 17;
+            (*state % (max_height + 1)) as u8
+        })
+    }
+
+    /// compares with the sequential analysis on forests from single trees and thin lines to rectangles
+    /// with few or many distinct heights
+    fn matches_sequential(seed: u64) {
+        let mut state = seed;
+        let shapes = [(1, 1), (1, 37), (53, 1), (2, 3), (64, 64), (65, 7), (120, 97)];
+        for (width, height) in shapes {
+            for max_height in [0, 1, 3, 9] {
+                let grid = random_forest(&mut state, width, height, max_height);
+                let visible = forest::visible_trees(&grid);
+                let scores = forest::scenic_scores(&grid).unwrap();
+                let parallel = analyse(&grid).unwrap();
+                for point in grid.points() {
+                    assert_eq!(parallel.visible.get(point), visible[point], "visibility at {:?} of {:?}", point, grid);
+                    assert_eq!(parallel.scores[point], scores[point] as u64, "score at {:?} of {:?}", point, grid);
+                }
+                assert_eq!(parallel.visible.count(), visible.iter().filter(|(_, visible)| **visible).count());
+            }
+        }
+    }
+
+    #[test]
+    fn matches_sequential_analysis() {
+        for seed in 1..20 {
+            matches_sequential(seed);
+        }
+    }
+
+    #[test]
+    fn matches_sequential_analysis_with_more_bands_than_columns() {
+        let pool = rayon::ThreadPoolBuilder::new().num_threads(40).build().unwrap();
+        pool.install(|| matches_sequential(0x5EED));
+    }
+
+    #[test]
+    fn puzzle_input() {
+        let grid: Grid<u8> = std::fs::read_to_string(concat!(env!("CARGO_MANIFEST_DIR"), "/input.txt")).unwrap().parse().unwrap();
+        let analysis = analyse(&grid).unwrap();
+        assert_eq!(analysis.visible.count(), 1812);
+        assert_eq!(analysis.max_score(), 315495);
+    }
+}
@@ -0,0 +1,131 @@
+use std::io;
+use std::io::ErrorKind;
+use crate::grid::{Grid, Point};
+
+/// Someone standing in the forest. Each tree fills its whole cell from the ground (height 0) up to its
+/// height, and the eye is `eye_height` above the ground at the centre of the observer's cell.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Observer {
+    pub position: Point,
+    pub eye_height: f64,
+}
+
+/// What an observer can see: the tops of these trees, as a list and as a map of the forest
+#[derive(Clone, Debug)]
+pub struct Viewshed {
+    pub visible: Vec<Point>,
+    pub map: Grid<bool>,
+}
+
+/// the fractions of the way along the segment where it crosses a grid line between `from` and `to`
+fn crossings(from: f64, to: f64, out: &mut Vec<f64>) {
+    if from == to {
+        return;
+    }
+    let (low, high) = if from < to { (from, to) } else { (to, from) };
+    let mut line = low.floor() + 1.0;
+    while line < high {
+        out.push((line - from) / (to - from));
+        line += 1.0;
+    }
+}
+
+/// Whether the top of the tree at `target` can be seen from the eye. The sightline is a straight segment
+/// from the eye to the centre of the target's top; it is blocked by any tree in between whose cell it
+/// crosses at or below that tree's height. Passing exactly through a corner touches no cell.
+fn line_of_sight<T: Copy + Into<f64>>(grid: &Grid<T>, observer: &Observer, target: Point, buffer: &mut Vec<f64>) -> bool {
+    let (row0, column0) = (observer.position.0 as f64 + 0.5, observer.position.1 as f64 + 0.5);
+    let (row1, column1) = (target.0 as f64 + 0.5, target.1 as f64 + 0.5);
+    let top: f64 = grid[target].into();
+    let elevation = |t: f64| observer.eye_height + (top - observer.eye_height) * t;
+
+    buffer.clear();
+    buffer.extend([0.0, 1.0]);
+    crossings(row0, row1, buffer);
+    crossings(column0, column1, buffer);
+    buffer.sort_by(f64::total_cmp);
+    for window in buffer.windows(2) {
+        let (start, end) = (window[0], window[1]);
+        if end - start < 1e-12 {
+            continue;
+        }
+        let middle = (start + end) / 2.0;
+        let cell = ((row0 + (row1 - row0) * middle).floor() as usize, (column0 + (column1 - column0) * middle).floor() as usize);
+        if cell == observer.position || cell == target {
+            continue;
+        }
+        let height: f64 = grid[cell].into();
+        // the sightline is straight, so its lowest point over this cell is where it enters or leaves
+        if height >= elevation(start).min(elevation(end)) {
+            return false;
+        }
+    }
+    true
+}
+
+/// Casts a ray from the observer's eye to the top of every other tree and keeps the ones it reaches,
+/// generalising visibility from the edges of the forest to any point inside it
+pub fn viewshed<T: Copy + Into<f64>>(grid: &Grid<T>, observer: &Observer) -> io::Result<Viewshed> {
+    if !grid.contains(observer.position) {
+        let message = format!("observer at {:?} is outside the {}x{} forest", observer.position, grid.width(), grid.height());
+        return Err(io::Error::new(ErrorKind::InvalidInput, message));
+    }
+    if !observer.eye_height.is_finite() {
+        return Err(io::Error::new(ErrorKind::InvalidInput, format!("eye height {} is not a finite number", observer.eye_height)));
+    }
+    let mut map = Grid::filled(grid.width(), grid.height(), false);
+    let mut visible = Vec::new();
+    let mut buffer = Vec::new();
+    for target in grid.points() {
+        if target != observer.position && line_of_sight(grid, observer, target, &mut buffer) {
+            map[target] = true;
+            visible.push(target);
+        }
+    }
+    Ok(Viewshed { visible, map })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// a 5x5 forest of height 0 with a wall of height 5 down the middle column
+    fn walled() -> Grid<u8> {
+        Grid::from_fn(5, 5, |(_, column)| if column == 2 { 5 } else { 0 })
+    }
+
+    fn sees(grid: &Grid<u8>, eye_height: f64) -> Grid<bool> {
+        viewshed(grid, &Observer { position: (2, 0), eye_height }).unwrap().map
+    }
+
+    #[test]
+    fn a_wall_blocks_the_view() {
+        let map = sees(&walled(), 1.0);
+        for row in 0..5 {
+            assert!(map[(row, 2)], "the wall itself is in sight at row {}", row);
+            assert!(!map[(row, 3)] && !map[(row, 4)], "row {} behind the wall is hidden", row);
+        }
+    }
+
+    #[test]
+    fn a_taller_eye_sees_over_the_wall() {
+        let map = sees(&walled(), 40.0);
+        assert!((0..5).all(|row| map[(row, 3)] && map[(row, 4)]));
+    }
+
+    #[test]
+    fn a_flat_forest_is_fully_visible() {
+        let grid = Grid::filled(5, 5, 0u8);
+        let viewshed = viewshed(&grid, &Observer { position: (1, 3), eye_height: 1.0 }).unwrap();
+        assert_eq!(viewshed.visible.len(), 24);
+        assert!(!viewshed.map[(1, 3)]);
+    }
+
+    #[test]
+    fn rejects_bad_observers() {
+        for eye_height in [f64::NAN, f64::INFINITY, f64::NEG_INFINITY] {
+            assert!(viewshed(&walled(), &Observer { position: (0, 0), eye_height }).is_err());
+        }
+        assert!(viewshed(&walled(), &Observer { position: (5, 0), eye_height: 1.0 }).is_err());
+    }
+}
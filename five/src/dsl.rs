@@ -0,0 +1,323 @@
+use std::error::Error;
+use std::fmt;
+use crate::Stacks;
+
+/// Location of a token or statement in the program text; lines and columns are 1-based
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Span {
+    pub line: usize,
+    pub column: usize,
+    pub start: usize,
+    pub end: usize,
+}
+
+impl Span {
+    fn to(self, other: Span) -> Span {
+        Span { end: other.end, ..self }
+    }
+}
+
+/// A parse or runtime error pointing at the offending part of the program
+#[derive(Debug)]
+pub struct DslError {
+    pub message: String,
+    pub span: Span,
+}
+
+impl DslError {
+    fn new(message: impl Into<String>, span: Span) -> DslError {
+        DslError { message: message.into(), span }
+    }
+}
+
+impl fmt::Display for DslError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}: {}", self.span.line, self.span.column, self.message)
+    }
+}
+
+impl Error for DslError {}
+
+#[derive(Clone, Debug, PartialEq)]
+enum TokenKind {
+    Word(String),
+    Number(i64),
+    OpenBrace,
+    CloseBrace,
+}
+
+#[derive(Clone, Debug)]
+struct Token {
+    kind: TokenKind,
+    span: Span,
+}
+
+/// Splits program text into words, integers and braces, dropping whitespace and `#` comments.
+/// `first_line` is the line number of the first line of `source` in the file it came from.
+fn tokenize(source: &str, first_line: usize) -> Result<Vec<Token>, DslError> {
+    let bytes = source.as_bytes();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+    let mut line = first_line;
+    let mut line_start = 0;
+    while i < bytes.len() {
+        let span_at = |start: usize, end: usize| Span { line, column: start - line_start + 1, start, end };
+        let c = bytes[i];
+        if c == b'\n' {
+            i += 1;
+            line += 1;
+            line_start = i;
+        } else if c.is_ascii_whitespace() {
+            i += 1;
+        } else if c == b'#' {
+            while i < bytes.len() && bytes[i] != b'\n' {
+                i += 1;
+            }
+        } else if c == b'{' || c == b'}' {
+            let kind = if c == b'{' { TokenKind::OpenBrace } else { TokenKind::CloseBrace };
+            tokens.push(Token { kind, span: span_at(i, i + 1) });
+            i += 1;
+        } else if c.is_ascii_digit() || c == b'-' {
+            let start = i;
+            i += 1;
+            while i < bytes.len() && bytes[i].is_ascii_digit() {
+                i += 1;
+            }
+            let span = span_at(start, i);
+            let number = source[start..i]
+                .parse()
+                .map_err(|_| DslError::new(format!("invalid number `{}`", &source[start..i]), span))?;
+            tokens.push(Token { kind: TokenKind::Number(number), span });
+        } else if c.is_ascii_alphabetic() {
+            let start = i;
+            while i < bytes.len() && (bytes[i].is_ascii_alphanumeric() || bytes[i] == b'_') {
+                i += 1;
+            }
+            tokens.push(Token { kind: TokenKind::Word(source[start..i].to_string()), span: span_at(start, i) });
+        } else {
+            let end = i + source[i..].chars().next().map_or(1, char::len_utf8);
+            return Err(DslError::new(format!("unexpected character `{}`", &source[i..end]), span_at(i, end)));
+        }
+    }
+    Ok(tokens)
+}
+
+/// A statement of the crate language; stack numbers are 1-based like in `move N from A to B`
+#[derive(Clone, Debug, PartialEq)]
+pub enum StatementKind {
+    Move { count: usize, from: usize, to: usize },
+    /// exchanges the whole contents of two stacks
+    Swap { a: usize, b: usize },
+    /// turns a stack upside down
+    Reverse { stack: usize },
+    /// moves the top `k` crates of a stack to its bottom, keeping their order; negative `k` rotates the other way
+    Rotate { stack: usize, k: i64 },
+    Repeat { times: usize, body: Vec<Statement> },
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Statement {
+    pub kind: StatementKind,
+    pub span: Span,
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    position: usize,
+    end: Span,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.position)
+    }
+
+    fn next(&mut self, expected: &str) -> Result<Token, DslError> {
+        let token = self
+            .tokens
+            .get(self.position)
+            .cloned()
+            .ok_or_else(|| DslError::new(format!("expected {}, found end of program", expected), self.end))?;
+        self.position += 1;
+        Ok(token)
+    }
+
+    fn keyword(&mut self, keyword: &str) -> Result<Span, DslError> {
+        let token = self.next(&format!("`{}`", keyword))?;
+        match token.kind {
+            TokenKind::Word(word) if word == keyword => Ok(token.span),
+            _ => Err(DslError::new(format!("expected `{}`", keyword), token.span)),
+        }
+    }
+
+    fn integer(&mut self, what: &str) -> Result<(i64, Span), DslError> {
+        let token = self.next(what)?;
+        match token.kind {
+            TokenKind::Number(number) => Ok((number, token.span)),
+            _ => Err(DslError::new(format!("expected {}", what), token.span)),
+        }
+    }
+
+    fn count(&mut self, what: &str) -> Result<(usize, Span), DslError> {
+        let (number, span) = self.integer(what)?;
+        let count = usize::try_from(number).map_err(|_| DslError::new(format!("{} can't be negative", what), span))?;
+        Ok((count, span))
+    }
+
+    fn stack(&mut self) -> Result<(usize, Span), DslError> {
+        let (stack, span) = self.count("a stack number")?;
+        if stack == 0 {
+            return Err(DslError::new("stacks are numbered from 1", span));
+        }
+        Ok((stack, span))
+    }
+
+    fn statement(&mut self) -> Result<Statement, DslError> {
+        let token = self.next("a statement")?;
+        let start = token.span;
+        let TokenKind::Word(word) = token.kind else {
+            return Err(DslError::new("expected a statement", start));
+        };
+        let (kind, end) = match word.as_str() {
+            "move" => {
+                let (count, _) = self.count("a crate count")?;
+                self.keyword("from")?;
+                let (from, _) = self.stack()?;
+                self.keyword("to")?;
+                let (to, end) = self.stack()?;
+                (StatementKind::Move { count, from, to }, end)
+            }
+            "swap" => {
+                let (a, _) = self.stack()?;
+                let (b, end) = self.stack()?;
+                (StatementKind::Swap { a, b }, end)
+            }
+            "reverse" => {
+                let (stack, end) = self.stack()?;
+                (StatementKind::Reverse { stack }, end)
+            }
+            "rotate" => {
+                let (stack, _) = self.stack()?;
+                let (k, end) = self.integer("a rotation amount")?;
+                (StatementKind::Rotate { stack, k }, end)
+            }
+            "repeat" => {
+                let (times, _) = self.count("a repeat count")?;
+                let open = self.next("`{`")?;
+                if open.kind != TokenKind::OpenBrace {
+                    return Err(DslError::new("expected `{`", open.span));
+                }
+                let mut body = Vec::new();
+                loop {
+                    match self.peek() {
+                        Some(Token { kind: TokenKind::CloseBrace, span }) => {
+                            let end = *span;
+                            self.position += 1;
+                            break (StatementKind::Repeat { times, body }, end);
+                        }
+                        Some(_) => body.push(self.statement()?),
+                        None => return Err(DslError::new("unclosed `{`", open.span)),
+                    }
+                }
+            }
+            other => return Err(DslError::new(format!("unknown statement `{}`", other), start)),
+        };
+        Ok(Statement { kind, span: start.to(end) })
+    }
+}
+
+/// Parses a program; plain `move N from A to B` files are valid programs
+pub fn parse(source: &str, first_line: usize) -> Result<Vec<Statement>, DslError> {
+    let tokens = tokenize(source, first_line)?;
+    let end = tokens.last().map_or(
+        Span { line: first_line, column: 1, start: 0, end: 0 },
+        |token| Span { start: token.span.end, ..token.span },
+    );
+    let mut parser = Parser { tokens, position: 0, end };
+    let mut statements = Vec::new();
+    while let Some(token) = parser.peek() {
+        if token.kind == TokenKind::CloseBrace {
+            return Err(DslError::new("unmatched `}`", token.span));
+        }
+        statements.push(parser.statement()?);
+    }
+    Ok(statements)
+}
+
+/// Runs a program against any crate backend, stopping at the first statement that can't be applied
+pub fn execute<S: Stacks>(program: &[Statement], crates: &mut S, in_place: bool) -> Result<(), DslError> {
+    for statement in program {
+        let check = |stack: usize| {
+            if stack > crates.number_of_stacks() {
+                Err(DslError::new(format!("there is no stack {}", stack), statement.span))
+            } else {
+                Ok(stack - 1)
+            }
+        };
+        match &statement.kind {
+            StatementKind::Move { count, from, to } => {
+                let (from, to) = (check(*from)?, check(*to)?);
+                if crates.height(from) < *count {
+                    let message = format!("stack {} only has {} crates", from + 1, crates.height(from));
+                    return Err(DslError::new(message, statement.span));
+                }
+                crates.move_crates(*count, from, to, in_place);
+            }
+            StatementKind::Swap { a, b } => {
+                let (a, b) = (check(*a)?, check(*b)?);
+                crates.swap(a, b);
+            }
+            StatementKind::Reverse { stack } => {
+                let stack = check(*stack)?;
+                crates.reverse(stack);
+            }
+            StatementKind::Rotate { stack, k } => {
+                let stack = check(*stack)?;
+                let height = crates.height(stack);
+                if height > 0 {
+                    crates.rotate(stack, k.rem_euclid(height as i64) as usize);
+                }
+            }
+            StatementKind::Repeat { times, body } => {
+                for _ in 0..*times {
+                    execute(body, crates, in_place)?;
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::BufRead;
+    use crate::build_instructions;
+
+    #[test]
+    fn instruction_files_parse_unchanged() {
+        let input = include_str!("../input.txt");
+        let (drawing, program) = input.split_once("\n\n").unwrap();
+        let first_line = drawing.lines().count() + 2;
+        let statements = parse(program, first_line).unwrap();
+        let instructions = build_instructions(&mut program.as_bytes().lines()).unwrap();
+        assert_eq!(statements.len(), instructions.len());
+        for (i, (statement, instruction)) in statements.iter().zip(&instructions).enumerate() {
+            let expected = StatementKind::Move { count: instruction.count, from: instruction.from, to: instruction.to };
+            assert_eq!(statement.kind, expected);
+            assert_eq!((statement.span.line, statement.span.column), (first_line + i, 1));
+        }
+    }
+
+    fn error_at(source: &str) -> (String, usize, usize) {
+        let error = parse(source, 1).unwrap_err();
+        (error.message, error.span.line, error.span.column)
+    }
+
+    #[test]
+    fn parse_errors_point_at_the_problem() {
+        assert_eq!(error_at("move 1 from 1 to 2\nrepeat 2 {\n  swap 1 2\n"), ("unclosed `{`".to_string(), 2, 10));
+        assert_eq!(error_at("reverse 1\n  }"), ("unmatched `}`".to_string(), 2, 3));
+        assert_eq!(error_at("move 1 from 0 to 2"), ("stacks are numbered from 1".to_string(), 1, 13));
+    }
+}
@@ -1,54 +1,206 @@
 use std::error::Error;
 use std::fs::File;
 use std::io;
-use std::io::{BufRead, BufReader, ErrorKind, Lines};
+use std::io::{BufRead, BufReader, ErrorKind, Lines, Read};
 use std::str::FromStr;
 use lazy_static::lazy_static;
 use regex::Regex;
+use dsl::{execute, parse};
+use plan::{plan, Target};
+use rope::RopeCrates;
+
+mod dsl;
+mod plan;
+mod rope;
 
 const INSTRUCTION_REGEX_STR: &str = r"^move (\d+) from (\d+) to (\d+)$";
 lazy_static! {
-    static ref instruction_regex: Regex = Regex::new(INSTRUCTION_REGEX_STR).unwrap();
+    static ref INSTRUCTION_REGEX: Regex = Regex::new(INSTRUCTION_REGEX_STR).unwrap();
 }
 
 fn main() -> Result<(), Box<dyn Error>> {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    match args.first().map(|arg| arg.as_str()) {
+        Some("plan") => return run_planner(&args),
+        Some("run") => return run_program(&args),
+        _ => {}
+    }
+
     let file = File::open("./five/input.txt")?;
-    let mut reader = BufReader::new(file);
+    let reader = BufReader::new(file);
     let mut lines = reader.lines();
-    let mut crates = build_crates(&mut lines, 9)?;
-    let mut crates_copy = crates.clone();
+    let crates = build_crates(&mut lines, 9)?;
     let instructions = build_instructions(&mut lines)?;
+    match flag_value(&args, "--backend").unwrap_or("vec") {
+        "vec" => print_answers(&crates, &instructions),
+        "rope" => print_answers(&RopeCrates::from(&crates), &instructions),
+        other => return Err(format!("unknown backend: {}", other).into()),
+    }
+
+    Ok(())
+}
+
+fn flag_value<'a>(args: &'a [String], flag: &str) -> Option<&'a str> {
+    let position = args.iter().position(|arg| arg == flag)?;
+    args.get(position + 1).map(|value| value.as_str())
+}
+
+/// `five plan [--from drawing] (--tops CMZ | --target drawing) [--crane 9000|9001] [--stacks N] [--depth N]`
+/// prints a program in the same format as `input.txt`
+fn run_planner(args: &[String]) -> Result<(), Box<dyn Error>> {
+    let number_of_stacks: usize = flag_value(args, "--stacks").unwrap_or("9").parse()?;
+    let max_depth: usize = flag_value(args, "--depth").unwrap_or("3").parse()?;
+    let in_place = match flag_value(args, "--crane").unwrap_or("9000") {
+        "9000" => false,
+        "9001" => true,
+        other => return Err(format!("unknown crane model: {}", other).into()),
+    };
+    let start_path = flag_value(args, "--from").unwrap_or("./header.txt");
+    let start = build_crates(&mut BufReader::new(File::open(start_path)?).lines(), number_of_stacks)?;
+    let target = if let Some(tops) = flag_value(args, "--tops") {
+        Target::tops(tops)
+    } else if let Some(target_path) = flag_value(args, "--target") {
+        let drawing = build_crates(&mut BufReader::new(File::open(target_path)?).lines(), number_of_stacks)?;
+        Target::Arrangement(drawing.crates)
+    } else {
+        return Err("plan needs either --tops or --target".into());
+    };
+    for instruction in plan(&start, &target, in_place, max_depth)? {
+        println!("{}", instruction);
+    }
+    Ok(())
+}
+
+/// `five run [file] [--crane 9000|9001] [--backend vec|rope] [--stacks N]` runs a file made of a
+/// drawing, a blank line and a program in the extended language, and prints the top of each stack
+fn run_program(args: &[String]) -> Result<(), Box<dyn Error>> {
+    let path = args.get(1).filter(|arg| !arg.starts_with("--")).map_or("./five/input.txt", |arg| arg.as_str());
+    let number_of_stacks: usize = flag_value(args, "--stacks").unwrap_or("9").parse()?;
+    let in_place = match flag_value(args, "--crane").unwrap_or("9000") {
+        "9000" => false,
+        "9001" => true,
+        other => return Err(format!("unknown crane model: {}", other).into()),
+    };
+    let mut text = String::new();
+    File::open(path)?.read_to_string(&mut text)?;
+    let mut lines = text.as_bytes().lines();
+    let crates = build_crates(&mut lines, number_of_stacks)?;
+    let source = lines.collect::<io::Result<Vec<String>>>()?;
+    let first_line = text.lines().count() - source.len() + 1;
+    let program = parse(&source.join("\n"), first_line).map_err(|e| format!("{}:{}", path, e))?;
+    let tops = match flag_value(args, "--backend").unwrap_or("vec") {
+        "vec" => {
+            let mut crates = crates;
+            execute(&program, &mut crates, in_place).map_err(|e| format!("{}:{}", path, e))?;
+            crates.tops()
+        }
+        "rope" => {
+            let mut crates = RopeCrates::from(&crates);
+            execute(&program, &mut crates, in_place).map_err(|e| format!("{}:{}", path, e))?;
+            crates.tops()
+        }
+        other => return Err(format!("unknown backend: {}", other).into()),
+    };
+    println!("{}", String::from_utf8(tops)?);
+    Ok(())
+}
+
+fn print_answers<S: Stacks + Clone>(crates: &S, instructions: &[Instruction]) {
+    let mut crates_copy = crates.clone();
+    let mut crates = crates.clone();
     for instruction in instructions.iter() {
         interpret_instruction(instruction, &mut crates, false)
     }
-    let mut result = Vec::with_capacity(9);
-    for c in crates.crates.iter_mut() {
-        result.push(c.pop().unwrap())
-    }
-    println!("answer 1: {}", String::from_utf8(result).unwrap());
+    println!("answer 1: {}", String::from_utf8(crates.tops()).unwrap());
 
     for instruction in instructions.iter() {
         interpret_instruction(instruction, &mut crates_copy, true)
     }
-    let mut result = Vec::with_capacity(9);
-    for c in crates_copy.crates.iter_mut() {
-        result.push(c.pop().unwrap())
-    }
-    println!("answer 2: {}", String::from_utf8(result).unwrap());
+    println!("answer 2: {}", String::from_utf8(crates_copy.tops()).unwrap());
+}
 
-    Ok(())
+/// Operations the instruction interpreter needs from a crate storage backend.
+/// Stacks are 0-indexed and ordered bottom to top.
+trait Stacks {
+    /// moves `count` crates from the top of `from` to the top of `to`, keeping their order when `in_place`;
+    /// moving crates from a stack onto itself leaves it unchanged
+    fn move_crates(&mut self, count: usize, from: usize, to: usize, in_place: bool);
+    /// the crate on top of each non-empty stack
+    fn tops(&self) -> Vec<u8>;
+    /// a copy of every stack, bottom to top
+    #[cfg(test)]
+    fn stacks(&self) -> Vec<Vec<u8>>;
+    fn number_of_stacks(&self) -> usize;
+    /// the number of crates in a stack
+    fn height(&self, stack: usize) -> usize;
+    /// exchanges the contents of two stacks
+    fn swap(&mut self, a: usize, b: usize);
+    /// turns a stack upside down
+    fn reverse(&mut self, stack: usize);
+    /// moves the top `k` crates of a stack to its bottom, keeping their order; `k` is taken modulo the
+    /// height, so rotating by the height or more wraps around
+    fn rotate(&mut self, stack: usize, k: usize);
 }
+
 #[derive(Clone, Debug)]
 struct Crates {
     crates: Vec<Vec<u8>>
 }
 
-fn build_crates(reader: &mut Lines<BufReader<File>>, expected_number: usize) -> io::Result<Crates> {
+impl Stacks for Crates {
+    fn move_crates(&mut self, count: usize, from: usize, to: usize, in_place: bool) {
+        if in_place {
+            let mut temp = Vec::with_capacity(count);
+            for _ in 0..count {
+                temp.push(self.crates[from].pop().unwrap());
+            }
+            temp.reverse();
+            self.crates[to].extend(temp);
+        } else {
+            for _ in 0..count {
+                let temp = self.crates[from].pop().unwrap();
+                self.crates[to].push(temp);
+            }
+        }
+    }
+
+    fn tops(&self) -> Vec<u8> {
+        self.crates.iter().filter_map(|c| c.last().copied()).collect()
+    }
+
+    #[cfg(test)]
+    fn stacks(&self) -> Vec<Vec<u8>> {
+        self.crates.clone()
+    }
+
+    fn number_of_stacks(&self) -> usize {
+        self.crates.len()
+    }
+
+    fn height(&self, stack: usize) -> usize {
+        self.crates[stack].len()
+    }
+
+    fn swap(&mut self, a: usize, b: usize) {
+        self.crates.swap(a, b)
+    }
+
+    fn reverse(&mut self, stack: usize) {
+        self.crates[stack].reverse()
+    }
+
+    fn rotate(&mut self, stack: usize, k: usize) {
+        let height = self.crates[stack].len();
+        self.crates[stack].rotate_right(k % height.max(1))
+    }
+}
+
+fn build_crates<B: BufRead>(reader: &mut Lines<B>, expected_number: usize) -> io::Result<Crates> {
     let mut crates: Vec<Vec<u8>> = Vec::with_capacity(expected_number);
-    for i in 0..expected_number {
+    for _ in 0..expected_number {
         crates.push(Vec::with_capacity(32));
     }
-    while let Some(next_line) = reader.next() {
+    for next_line in reader.by_ref() {
         let line = next_line?;
         let bytes = line.as_bytes();
         if bytes.is_empty() || bytes[0].is_ascii_digit() {
@@ -75,7 +227,10 @@ fn build_crates(reader: &mut Lines<BufReader<File>>, expected_number: usize) ->
                 let next_num = bytes[i];
                 i += 2; //advance past number and closing ]
 
-                crates[index].push(next_num);
+                let stack = crates.get_mut(index).ok_or_else(|| {
+                    io::Error::new(ErrorKind::InvalidData, format!("drawing has more than {} stacks", expected_number))
+                })?;
+                stack.push(next_num);
 
                 index += 1
             }
@@ -84,7 +239,7 @@ fn build_crates(reader: &mut Lines<BufReader<File>>, expected_number: usize) ->
         }
 
     };
-    for mut c in crates.iter_mut() {
+    for c in crates.iter_mut() {
         c.reverse()
     }
 
@@ -99,11 +254,17 @@ struct Instruction {
     from: usize,
     to: usize
 }
+impl std::fmt::Display for Instruction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "move {} from {} to {}", self.count, self.from, self.to)
+    }
+}
+
 impl FromStr for Instruction {
     type Err = io::Error;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        let captures = instruction_regex.captures(s).ok_or(io::Error::new(ErrorKind::InvalidData, "failed to parse instruction"))?;
+        let captures = INSTRUCTION_REGEX.captures(s).ok_or(io::Error::new(ErrorKind::InvalidData, "failed to parse instruction"))?;
         let instruction = Instruction{
             count: captures[1].parse().unwrap(),
             from: captures[2].parse().unwrap(),
@@ -114,9 +275,9 @@ impl FromStr for Instruction {
     }
 }
 
-fn build_instructions(reader: &mut Lines<BufReader<File>>) -> io::Result<Vec<Instruction>> {
+fn build_instructions<B: BufRead>(reader: &mut Lines<B>) -> io::Result<Vec<Instruction>> {
     let mut instructions: Vec<Instruction> = Vec::with_capacity(128);
-    while let Some(next_line) = reader.next() {
+    for next_line in reader {
         let next_line = next_line?;
         let instruction: Instruction = next_line.parse()?;
         instructions.push(instruction);
@@ -125,23 +286,75 @@ fn build_instructions(reader: &mut Lines<BufReader<File>>) -> io::Result<Vec<Ins
     Ok(instructions)
 }
 
-fn interpret_instruction(instruction: &Instruction, crates: &mut Crates, in_place: bool)  {
+fn interpret_instruction<S: Stacks>(instruction: &Instruction, crates: &mut S, in_place: bool) {
+    crates.move_crates(instruction.count, instruction.from - 1, instruction.to - 1, in_place)
+}
 
-    let from = instruction.from - 1;
-    let to = instruction.to - 1;
-    if in_place {
-        let mut temp = Vec::with_capacity(instruction.count);
-        for i in 0..instruction.count {
-            temp.push( crates.crates[from].pop().unwrap());
-        }
-        temp.reverse();
-        crates.crates[to].extend(temp);
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rope::XorShift;
 
-    } else {
-        for i in 0..instruction.count {
-            let temp = crates.crates[from].pop().unwrap();
-            crates.crates[to].push(temp);
+    /// Runs randomly generated programs on the naive `Crates` and the `RopeCrates` backend and
+    /// panics on the first program whose final stacks differ
+    fn differential_check(rounds: usize, seed: u32) {
+        let mut rng = XorShift::new(seed);
+        for round in 0..rounds {
+            let number_of_stacks = 1 + rng.below(9);
+            let mut crates = Crates { crates: vec![Vec::new(); number_of_stacks] };
+            for _ in 0..rng.below(200) {
+                let stack = rng.below(number_of_stacks);
+                crates.crates[stack].push(b'A' + rng.below(26) as u8);
+            }
+            let mut rope = RopeCrates::from(&crates);
+            let in_place = rng.below(2) == 0;
+            for _ in 0..rng.below(500) {
+                let from = rng.below(number_of_stacks);
+                let to = rng.below(number_of_stacks);
+                let available = crates.crates[from].len();
+                if available == 0 {
+                    continue;
+                }
+                match rng.below(8) {
+                    0 => {
+                        crates.swap(from, to);
+                        rope.swap(from, to);
+                    }
+                    1 => {
+                        crates.reverse(from);
+                        rope.reverse(from);
+                    }
+                    2 => {
+                        let k = rng.below(2 * available + 1);
+                        crates.rotate(from, k);
+                        rope.rotate(from, k);
+                    }
+                    _ => {
+                        let instruction = Instruction { count: 1 + rng.below(available), from: from + 1, to: to + 1 };
+                        interpret_instruction(&instruction, &mut crates, in_place);
+                        interpret_instruction(&instruction, &mut rope, in_place);
+                    }
+                }
+            }
+            assert_eq!(crates.stacks(), rope.stacks(), "backends diverged in round {}", round);
+            assert_eq!(crates.tops(), rope.tops(), "backends diverged in round {}", round);
         }
+    }
+
+    #[test]
+    fn rope_matches_vec() {
+        differential_check(2000, 0xC0FFEE);
+    }
 
+    #[test]
+    fn moving_onto_the_same_stack_changes_nothing() {
+        for in_place in [false, true] {
+            let mut crates = Crates { crates: vec![b"AB".to_vec(), b"CD".to_vec()] };
+            let mut rope = RopeCrates::from(&crates);
+            crates.move_crates(2, 0, 0, in_place);
+            rope.move_crates(2, 0, 0, in_place);
+            assert_eq!(crates.stacks(), vec![b"AB".to_vec(), b"CD".to_vec()]);
+            assert_eq!(rope.stacks(), crates.stacks());
+        }
     }
 }
@@ -0,0 +1,333 @@
+use std::io;
+use std::io::ErrorKind;
+use crate::{Crates, Instruction};
+
+/// Number of states the bounded search may visit before settling for the constructive plan
+const SEARCH_BUDGET: usize = 200_000;
+
+/// What the planned program has to reach
+#[derive(Clone, Debug)]
+pub enum Target {
+    /// the crate wanted on top of each stack, `None` for stacks we don't care about
+    Tops(Vec<Option<u8>>),
+    /// the exact contents of every stack, bottom to top
+    Arrangement(Vec<Vec<u8>>),
+}
+
+impl Target {
+    /// parses a top-of-stack string like `CMZ`, where `_` leaves a stack unconstrained
+    pub fn tops(s: &str) -> Target {
+        Target::Tops(s.bytes().map(|b| if b == b'_' { None } else { Some(b) }).collect())
+    }
+
+    fn reached(&self, stacks: &[Vec<u8>]) -> bool {
+        self.mismatches(stacks) == 0
+    }
+
+    /// stacks that still differ from the target; a move touches two stacks so half of this is a lower bound
+    fn mismatches(&self, stacks: &[Vec<u8>]) -> usize {
+        match self {
+            Target::Tops(tops) => tops
+                .iter()
+                .zip(stacks)
+                .filter(|(top, stack)| top.is_some() && stack.last() != top.as_ref())
+                .count(),
+            Target::Arrangement(target) => target.iter().zip(stacks).filter(|(t, s)| t != s).count(),
+        }
+    }
+
+    fn check_feasible(&self, stacks: &[Vec<u8>]) -> io::Result<()> {
+        let mut available = [0usize; 256];
+        for c in stacks.iter().flatten() {
+            available[*c as usize] += 1;
+        }
+        let mut wanted = [0usize; 256];
+        match self {
+            Target::Tops(tops) => {
+                if tops.len() > stacks.len() {
+                    return Err(invalid_target("more tops than stacks"));
+                }
+                for c in tops.iter().flatten() {
+                    wanted[*c as usize] += 1;
+                }
+                if wanted.iter().zip(available.iter()).any(|(w, a)| w > a) {
+                    return Err(invalid_target("not enough crates for the requested tops"));
+                }
+            }
+            Target::Arrangement(target) => {
+                if target.len() != stacks.len() {
+                    return Err(invalid_target("target has a different number of stacks"));
+                }
+                for c in target.iter().flatten() {
+                    wanted[*c as usize] += 1;
+                }
+                if wanted != available {
+                    return Err(invalid_target("target doesn't contain the same crates as the start"));
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+fn invalid_target(message: &str) -> io::Error {
+    io::Error::new(ErrorKind::InvalidData, message)
+}
+
+/// Finds a short program that turns `start` into `target` when run with the given crane model.
+/// A bounded iterative-deepening search looks for programs of at most `max_depth` moves; when it
+/// finds nothing shorter, a constructive plan (at most a few moves per crate or stack) is returned.
+pub fn plan(start: &Crates, target: &Target, in_place: bool, max_depth: usize) -> io::Result<Vec<Instruction>> {
+    let stacks = &start.crates;
+    target.check_feasible(stacks)?;
+    let constructive = match target {
+        Target::Tops(tops) => construct_tops(stacks, tops, in_place),
+        Target::Arrangement(arrangement) => construct_arrangement(stacks, arrangement, in_place),
+    };
+    let limit = match &constructive {
+        Some(program) => max_depth.min(program.len().saturating_sub(1)),
+        None => max_depth,
+    };
+    let program = search(stacks, target, in_place, limit)
+        .or(constructive)
+        .ok_or_else(|| invalid_target("no program found, the constructive planner needs at least 3 stacks"))?;
+
+    let mut check = start.clone();
+    for instruction in program.iter() {
+        crate::interpret_instruction(instruction, &mut check, in_place);
+    }
+    if !target.reached(&check.crates) {
+        return Err(invalid_target("planned program doesn't reach the target"));
+    }
+    Ok(program)
+}
+
+fn apply(stacks: &mut [Vec<u8>], program: &mut Vec<Instruction>, count: usize, from: usize, to: usize, in_place: bool) {
+    let split = stacks[from].len() - count;
+    let mut block = stacks[from].split_off(split);
+    if !in_place {
+        block.reverse();
+    }
+    stacks[to].extend(block);
+    program.push(Instruction { count, from: from + 1, to: to + 1 });
+}
+
+fn common_prefix(a: &[u8], b: &[u8]) -> usize {
+    a.iter().zip(b).take_while(|(x, y)| x == y).count()
+}
+
+/// Builds the target stacks in order from the bottom up without ever disturbing a correctly placed prefix:
+/// clear whatever sits on top of the stack being built, dig out the longest block that continues it,
+/// and move that block across. Crates in the way go to a stack that isn't built yet when there is one,
+/// otherwise they are parked on a built stack and put back straight after.
+fn construct_arrangement(start: &[Vec<u8>], target: &[Vec<u8>], in_place: bool) -> Option<Vec<Instruction>> {
+    let mut stacks = start.to_vec();
+    let mut program = Vec::new();
+    let n = stacks.len();
+    loop {
+        let prefix: Vec<usize> = (0..n).map(|i| common_prefix(&stacks[i], &target[i])).collect();
+        let built = |i: usize| prefix[i] == target[i].len();
+        let Some(s) = (0..n).find(|s| !built(*s)) else { break };
+        if n < 3 {
+            return None;
+        }
+        if stacks[s].len() > prefix[s] {
+            let w = (0..n).filter(|w| *w != s).min_by_key(|w| built(*w))?;
+            let count = stacks[s].len() - prefix[s];
+            apply(&mut stacks, &mut program, count, s, w, in_place);
+            continue;
+        }
+        let needed = &target[s][prefix[s]..];
+        // (block length, crates to dig away, stack)
+        let mut best: Option<(usize, usize, usize)> = None;
+        for u in (0..n).filter(|u| *u != s) {
+            for q in prefix[u]..stacks[u].len() {
+                let available = (stacks[u].len() - q).min(needed.len());
+                for m in 1..=available {
+                    let block = &stacks[u][q..q + m];
+                    let lands_as_needed = if in_place {
+                        block == &needed[..m]
+                    } else {
+                        block.iter().rev().eq(needed[..m].iter())
+                    };
+                    if !lands_as_needed {
+                        continue;
+                    }
+                    let dig = stacks[u].len() - q - m;
+                    let better = match best {
+                        None => true,
+                        Some((best_m, best_dig, _)) => m > best_m || (m == best_m && dig < best_dig),
+                    };
+                    if better {
+                        best = Some((m, dig, u));
+                    }
+                }
+            }
+        }
+        let (m, dig, u) = best?;
+        if dig > 0 {
+            let w = (0..n).filter(|w| *w != s && *w != u).min_by_key(|w| built(*w))?;
+            let parked = built(w);
+            apply(&mut stacks, &mut program, dig, u, w, in_place);
+            apply(&mut stacks, &mut program, m, u, s, in_place);
+            if parked {
+                apply(&mut stacks, &mut program, dig, w, u, in_place);
+            }
+        } else {
+            apply(&mut stacks, &mut program, m, u, s, in_place);
+        }
+    }
+    Some(program)
+}
+
+/// Fixes the wanted tops one stack at a time, never touching the top of an already fixed stack
+/// for longer than it takes to borrow a crate from underneath it
+fn construct_tops(start: &[Vec<u8>], tops: &[Option<u8>], in_place: bool) -> Option<Vec<Instruction>> {
+    let mut stacks = start.to_vec();
+    let mut program = Vec::new();
+    let n = stacks.len();
+    let mut fixed = vec![false; n];
+    for (i, top) in tops.iter().enumerate() {
+        let Some(c) = *top else { continue };
+        if stacks[i].last() != Some(&c) {
+            // (moves needed, stack, position)
+            let mut best: Option<(usize, usize, usize)> = None;
+            for (u, stack) in stacks.iter().enumerate() {
+                for (q, crate_) in stack.iter().enumerate() {
+                    let is_top = q == stack.len() - 1;
+                    if *crate_ != c || (u == i && is_top) || (fixed[u] && is_top) {
+                        continue;
+                    }
+                    let cost = if u == i {
+                        4
+                    } else if fixed[u] {
+                        3
+                    } else if is_top {
+                        1
+                    } else {
+                        2
+                    };
+                    if best.is_none_or(|(b, _, _)| cost < b) {
+                        best = Some((cost, u, q));
+                    }
+                }
+            }
+            let (_, u, q) = best?;
+            let above = stacks[u].len() - q - 1;
+            if u == i {
+                let j = (0..n).find(|j| *j != i)?;
+                let k = (0..n).find(|k| *k != i && *k != j)?;
+                apply(&mut stacks, &mut program, above, i, j, in_place);
+                apply(&mut stacks, &mut program, 1, i, k, in_place);
+                apply(&mut stacks, &mut program, above, j, i, in_place);
+                apply(&mut stacks, &mut program, 1, k, i, in_place);
+            } else if fixed[u] {
+                let w = (0..n).find(|w| *w != i && *w != u)?;
+                apply(&mut stacks, &mut program, above, u, w, in_place);
+                apply(&mut stacks, &mut program, 1, u, i, in_place);
+                apply(&mut stacks, &mut program, above, w, u, in_place);
+            } else {
+                if above > 0 {
+                    apply(&mut stacks, &mut program, above, u, i, in_place);
+                }
+                apply(&mut stacks, &mut program, 1, u, i, in_place);
+            }
+        }
+        fixed[i] = true;
+    }
+    Some(program)
+}
+
+/// Iterative-deepening search for a program of at most `limit` moves, pruned by the mismatch lower bound
+fn search(start: &[Vec<u8>], target: &Target, in_place: bool, limit: usize) -> Option<Vec<Instruction>> {
+    let mut budget = SEARCH_BUDGET;
+    let mut stacks = start.to_vec();
+    let mut program = Vec::new();
+    for depth in 0..=limit {
+        if deepen(&mut stacks, target, in_place, depth, &mut program, &mut budget) {
+            return Some(program);
+        }
+        if budget == 0 {
+            break;
+        }
+    }
+    None
+}
+
+fn deepen(stacks: &mut Vec<Vec<u8>>, target: &Target, in_place: bool, depth: usize, program: &mut Vec<Instruction>, budget: &mut usize) -> bool {
+    if target.reached(stacks) {
+        return true;
+    }
+    if target.mismatches(stacks).div_ceil(2) > depth {
+        return false;
+    }
+    let n = stacks.len();
+    for from in 0..n {
+        for to in (0..n).filter(|to| *to != from) {
+            for count in 1..=stacks[from].len() {
+                if let Some(last) = program.last() {
+                    if last.from == to + 1 && last.to == from + 1 && last.count == count {
+                        continue;
+                    }
+                }
+                if *budget == 0 {
+                    return false;
+                }
+                *budget -= 1;
+                apply(stacks, program, count, from, to, in_place);
+                if deepen(stacks, target, in_place, depth - 1, program, budget) {
+                    return true;
+                }
+                // moving the block straight back restores it under either crane model
+                apply(stacks, program, count, to, from, in_place);
+                program.truncate(program.len() - 2);
+            }
+        }
+    }
+    false
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::BufRead;
+    use crate::{build_crates, build_instructions, interpret_instruction};
+
+    const DRAWING: &str = "    [D]    \n[N] [C]    \n[Z] [M] [P]\n 1   2   3 \n";
+
+    /// plans, prints the program, reads it back like an input file and runs it from the drawing
+    fn reaches(target: &Target, in_place: bool) -> Vec<Vec<u8>> {
+        let start = build_crates(&mut DRAWING.as_bytes().lines(), 3).unwrap();
+        let program: Vec<String> = plan(&start, target, in_place, 3).unwrap().iter().map(|instruction| instruction.to_string()).collect();
+        let instructions = build_instructions(&mut program.join("\n").as_bytes().lines()).unwrap();
+        let mut crates = start;
+        for instruction in &instructions {
+            interpret_instruction(instruction, &mut crates, in_place);
+        }
+        assert!(target.reached(&crates.crates), "{:?} doesn't reach {:?}", program, target);
+        crates.crates
+    }
+
+    #[test]
+    fn plans_tops_for_both_cranes() {
+        for in_place in [false, true] {
+            let stacks = reaches(&Target::tops("CMZ"), in_place);
+            let tops: Vec<u8> = stacks.iter().filter_map(|stack| stack.last().copied()).collect();
+            assert_eq!(tops, b"CMZ");
+        }
+    }
+
+    #[test]
+    fn plans_arrangements_for_both_cranes() {
+        let arrangement = vec![b"C".to_vec(), b"DNM".to_vec(), b"ZP".to_vec()];
+        for in_place in [false, true] {
+            assert_eq!(reaches(&Target::Arrangement(arrangement.clone()), in_place), arrangement);
+        }
+    }
+
+    #[test]
+    fn rejects_drawings_wider_than_the_stack_count() {
+        let error = build_crates(&mut DRAWING.as_bytes().lines(), 2).unwrap_err();
+        assert_eq!(error.kind(), ErrorKind::InvalidData);
+    }
+}
@@ -0,0 +1,245 @@
+use crate::{Crates, Stacks};
+
+const NIL: usize = usize::MAX;
+
+/// A node of an implicit treap; its position in the stack is given by the sizes of the subtrees to its left
+#[derive(Clone, Debug)]
+struct Node {
+    value: u8,
+    priority: u32,
+    size: usize,
+    reversed: bool,
+    left: usize,
+    right: usize,
+}
+
+/// Crate stacks stored as implicit treaps sharing one node arena.
+/// Moving a block of crates is a split of one treap and a merge into another, so it takes
+/// expected O(log n) time no matter how many crates are moved; the CrateMover 9000 reversal
+/// is a lazily propagated flag on the moved block.
+#[derive(Clone, Debug)]
+pub struct RopeCrates {
+    nodes: Vec<Node>,
+    roots: Vec<usize>,
+    rng: XorShift,
+}
+
+impl RopeCrates {
+    pub fn new(number_of_stacks: usize) -> RopeCrates {
+        RopeCrates {
+            nodes: Vec::with_capacity(1024),
+            roots: vec![NIL; number_of_stacks],
+            rng: XorShift::new(0x9E37_79B9),
+        }
+    }
+
+    /// pushes a single crate onto the top of a stack
+    pub fn push(&mut self, stack: usize, value: u8) {
+        let priority = self.rng.next_u32();
+        self.nodes.push(Node {
+            value,
+            priority,
+            size: 1,
+            reversed: false,
+            left: NIL,
+            right: NIL,
+        });
+        let node = self.nodes.len() - 1;
+        self.roots[stack] = self.merge(self.roots[stack], node);
+    }
+
+    fn size(&self, node: usize) -> usize {
+        if node == NIL {
+            0
+        } else {
+            self.nodes[node].size
+        }
+    }
+
+    fn update(&mut self, node: usize) {
+        let size = 1 + self.size(self.nodes[node].left) + self.size(self.nodes[node].right);
+        self.nodes[node].size = size;
+    }
+
+    fn toggle(&mut self, node: usize) {
+        if node != NIL {
+            self.nodes[node].reversed = !self.nodes[node].reversed;
+        }
+    }
+
+    /// applies a pending reversal to a node's children
+    fn push_down(&mut self, node: usize) {
+        if self.nodes[node].reversed {
+            let Node { left, right, .. } = self.nodes[node];
+            self.nodes[node].left = right;
+            self.nodes[node].right = left;
+            self.nodes[node].reversed = false;
+            self.toggle(left);
+            self.toggle(right);
+        }
+    }
+
+    /// splits a treap into its first `count` elements and the rest
+    fn split(&mut self, node: usize, count: usize) -> (usize, usize) {
+        if node == NIL {
+            return (NIL, NIL);
+        }
+        self.push_down(node);
+        let left = self.nodes[node].left;
+        let left_size = self.size(left);
+        if left_size < count {
+            let (a, b) = self.split(self.nodes[node].right, count - left_size - 1);
+            self.nodes[node].right = a;
+            self.update(node);
+            (node, b)
+        } else {
+            let (a, b) = self.split(left, count);
+            self.nodes[node].left = b;
+            self.update(node);
+            (a, node)
+        }
+    }
+
+    /// concatenates two treaps, every element of `a` ending up before every element of `b`
+    fn merge(&mut self, a: usize, b: usize) -> usize {
+        if a == NIL {
+            return b;
+        }
+        if b == NIL {
+            return a;
+        }
+        if self.nodes[a].priority > self.nodes[b].priority {
+            self.push_down(a);
+            let right = self.merge(self.nodes[a].right, b);
+            self.nodes[a].right = right;
+            self.update(a);
+            a
+        } else {
+            self.push_down(b);
+            let left = self.merge(a, self.nodes[b].left);
+            self.nodes[b].left = left;
+            self.update(b);
+            b
+        }
+    }
+
+    /// the crate on top of a stack, resolving pending reversals without mutating the treap
+    fn top(&self, stack: usize) -> Option<u8> {
+        let mut node = self.roots[stack];
+        let mut reversed = false;
+        while node != NIL {
+            reversed ^= self.nodes[node].reversed;
+            let next = if reversed { self.nodes[node].left } else { self.nodes[node].right };
+            if next == NIL {
+                return Some(self.nodes[node].value);
+            }
+            node = next;
+        }
+        None
+    }
+
+    #[cfg(test)]
+    fn collect(&self, node: usize, reversed: bool, out: &mut Vec<u8>) {
+        if node == NIL {
+            return;
+        }
+        let reversed = reversed ^ self.nodes[node].reversed;
+        let Node { left, right, value, .. } = self.nodes[node];
+        let (first, second) = if reversed { (right, left) } else { (left, right) };
+        self.collect(first, reversed, out);
+        out.push(value);
+        self.collect(second, reversed, out);
+    }
+}
+
+impl From<&Crates> for RopeCrates {
+    fn from(crates: &Crates) -> Self {
+        let mut rope = RopeCrates::new(crates.crates.len());
+        for (i, stack) in crates.crates.iter().enumerate() {
+            for c in stack {
+                rope.push(i, *c);
+            }
+        }
+        rope
+    }
+}
+
+impl Stacks for RopeCrates {
+    fn move_crates(&mut self, count: usize, from: usize, to: usize, in_place: bool) {
+        let length = self.height(from);
+        assert!(count <= length, "can't move {} crates from a stack of {}", count, length);
+        // splitting and merging back would reverse the block for the CrateMover 9000
+        if from == to {
+            return;
+        }
+        let (rest, block) = self.split(self.roots[from], length - count);
+        if !in_place {
+            self.toggle(block);
+        }
+        self.roots[from] = rest;
+        self.roots[to] = self.merge(self.roots[to], block);
+    }
+
+    fn tops(&self) -> Vec<u8> {
+        (0..self.roots.len()).filter_map(|i| self.top(i)).collect()
+    }
+
+    #[cfg(test)]
+    fn stacks(&self) -> Vec<Vec<u8>> {
+        self.roots
+            .iter()
+            .map(|root| {
+                let mut stack = Vec::with_capacity(self.size(*root));
+                self.collect(*root, false, &mut stack);
+                stack
+            })
+            .collect()
+    }
+
+    fn number_of_stacks(&self) -> usize {
+        self.roots.len()
+    }
+
+    fn height(&self, stack: usize) -> usize {
+        self.size(self.roots[stack])
+    }
+
+    fn swap(&mut self, a: usize, b: usize) {
+        self.roots.swap(a, b)
+    }
+
+    fn reverse(&mut self, stack: usize) {
+        self.toggle(self.roots[stack])
+    }
+
+    fn rotate(&mut self, stack: usize, k: usize) {
+        let height = self.height(stack);
+        let (bottom, top) = self.split(self.roots[stack], height - k % height.max(1));
+        self.roots[stack] = self.merge(top, bottom);
+    }
+}
+
+/// Small deterministic xorshift generator, used for treap priorities and the generated test programs
+#[derive(Clone, Debug)]
+pub struct XorShift(u32);
+
+impl XorShift {
+    pub fn new(seed: u32) -> XorShift {
+        XorShift(seed.max(1))
+    }
+
+    pub fn next_u32(&mut self) -> u32 {
+        let mut x = self.0;
+        x ^= x << 13;
+        x ^= x >> 17;
+        x ^= x << 5;
+        self.0 = x;
+        x
+    }
+
+    /// a number in `0..bound`
+    #[cfg(test)]
+    pub fn below(&mut self, bound: usize) -> usize {
+        self.next_u32() as usize % bound
+    }
+}
@@ -0,0 +1,307 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::io;
+use std::io::ErrorKind;
+use std::rc::Rc;
+use std::str::FromStr;
+use crate::{FileSystem, FileSystemEntry, Path};
+
+/// the exact solver gives up when merging two subtrees would combine more pairs of sums than this
+const EXACT_WORK_LIMIT: usize = 4_000_000;
+/// the exact solver gives up when a subtree has more distinct reachable sums than this
+const EXACT_STATE_LIMIT: usize = 50_000;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Solver {
+    /// exact when the tree is small enough, greedy otherwise
+    Auto,
+    Exact,
+    Greedy,
+}
+
+impl FromStr for Solver {
+    type Err = io::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "auto" => Ok(Solver::Auto),
+            "exact" => Ok(Solver::Exact),
+            "greedy" => Ok(Solver::Greedy),
+            _ => Err(io::Error::new(ErrorKind::InvalidInput, "solver must be auto, exact or greedy")),
+        }
+    }
+}
+
+/// One file or directory to delete
+#[derive(Clone, Debug)]
+pub struct Deletion {
+    pub path: Path,
+    pub size: u64,
+    pub directory: bool,
+}
+
+/// Entries to delete, none inside another, and whether the total is known to be the smallest possible
+#[derive(Clone, Debug)]
+pub struct Plan {
+    pub deletions: Vec<Deletion>,
+    pub reclaimed: u64,
+    pub exact: bool,
+}
+
+/// An entry that may be deleted, with the slab indices of the directories above it
+struct Candidate {
+    index: usize,
+    path: Path,
+    size: u64,
+    directory: bool,
+    ancestors: Vec<usize>,
+}
+
+/// Entries chosen by the exact solver, joined as subtrees are merged so sums share their history
+enum Picks {
+    One(usize),
+    Both(Rc<Picks>, Rc<Picks>),
+}
+
+type Selection = Option<Rc<Picks>>;
+
+fn join(a: &Selection, b: &Selection) -> Selection {
+    match (a, b) {
+        (None, other) | (other, None) => other.clone(),
+        (Some(a), Some(b)) => Some(Rc::new(Picks::Both(a.clone(), b.clone()))),
+    }
+}
+
+fn flatten(selection: &Selection, out: &mut Vec<usize>) {
+    let mut pending: Vec<&Picks> = selection.iter().map(|picks| picks.as_ref()).collect();
+    while let Some(picks) = pending.pop() {
+        match picks {
+            Picks::One(index) => out.push(*index),
+            Picks::Both(a, b) => pending.extend([a.as_ref(), b.as_ref()]),
+        }
+    }
+}
+
+/// whether deleting `path` would delete a protected entry or delete something inside one
+fn is_protected(path: &[String], protected: &[Path]) -> bool {
+    protected.iter().any(|protected| protected.starts_with(path) || path.starts_with(protected))
+}
+
+fn collect_candidates(filesystem: &FileSystem, directory: usize, path: &mut Path, ancestors: &mut Vec<usize>, protected: &[Path], out: &mut Vec<Candidate>) {
+    for (name, index) in filesystem.sorted_children(directory) {
+        path.push(name.clone());
+        let inside_protected = protected.iter().any(|protected| path.starts_with(protected));
+        if !inside_protected {
+            let directory = matches!(filesystem.entries[index], FileSystemEntry::DirectoryEntry(_));
+            if !is_protected(path, protected) {
+                let size = filesystem.total_size(index);
+                out.push(Candidate { index, path: path.clone(), size, directory, ancestors: ancestors.clone() });
+            }
+            if directory {
+                ancestors.push(index);
+                collect_candidates(filesystem, index, path, ancestors, protected, out);
+                ancestors.pop();
+            }
+        }
+        path.pop();
+    }
+}
+
+/// Finds the smallest total of non-nested entries that frees at least `need` bytes without touching
+/// any `protected` path
+pub fn plan(filesystem: &FileSystem, need: u64, protected: &[Path], solver: Solver) -> io::Result<Plan> {
+    if need == 0 {
+        return Ok(Plan { deletions: Vec::new(), reclaimed: 0, exact: true });
+    }
+    let mut candidates = Vec::new();
+    collect_candidates(filesystem, filesystem.root, &mut Vec::new(), &mut Vec::new(), protected, &mut candidates);
+    let by_index: HashMap<usize, &Candidate> = candidates.iter().map(|candidate| (candidate.index, candidate)).collect();
+
+    let reclaimable: u64 = candidates
+        .iter()
+        .filter(|candidate| !candidate.ancestors.iter().any(|ancestor| by_index.contains_key(ancestor)))
+        .map(|candidate| candidate.size)
+        .sum();
+    if reclaimable < need {
+        let message = format!("only {} bytes can be deleted without touching protected paths, {} needed", reclaimable, need);
+        return Err(io::Error::new(ErrorKind::InvalidData, message));
+    }
+
+    let exact = match solver {
+        Solver::Greedy => None,
+        Solver::Auto | Solver::Exact => exact(filesystem, filesystem.root, need, &by_index).map(|states| {
+            let (_, selection) = states.range(need..).next().expect("everything reclaimable reaches the need");
+            let mut picks = Vec::new();
+            flatten(selection, &mut picks);
+            picks
+        }),
+    };
+    if solver == Solver::Exact && exact.is_none() {
+        return Err(io::Error::new(ErrorKind::InvalidInput, "the tree is too large for the exact solver"));
+    }
+    let is_exact = exact.is_some();
+    let picks = exact.unwrap_or_else(|| greedy(&candidates, need));
+
+    let mut deletions: Vec<Deletion> = picks
+        .into_iter()
+        .map(|index| {
+            let candidate = by_index[&index];
+            Deletion { path: candidate.path.clone(), size: candidate.size, directory: candidate.directory }
+        })
+        .collect();
+    deletions.sort_by(|a, b| b.size.cmp(&a.size).then_with(|| a.path.cmp(&b.path)));
+    let reclaimed = deletions.iter().map(|deletion| deletion.size).sum();
+    Ok(Plan { deletions, reclaimed, exact: is_exact })
+}
+
+/// Every total reachable by deleting non-nested entries below `index` (or `index` itself), with the
+/// entries that reach it. Totals of at least `need` are pruned down to the smallest one.
+/// Returns None when the subtree has too many distinct totals to enumerate.
+fn exact(filesystem: &FileSystem, index: usize, need: u64, candidates: &HashMap<usize, &Candidate>) -> Option<BTreeMap<u64, Selection>> {
+    let mut states: BTreeMap<u64, Selection> = BTreeMap::from([(0, None)]);
+    if let FileSystemEntry::DirectoryEntry(_) = &filesystem.entries[index] {
+        // in name order, so ties between plans always go the same way
+        for (_, child) in filesystem.sorted_children(index) {
+            let child_states = exact(filesystem, child, need, candidates)?;
+            if states.len() * child_states.len() > EXACT_WORK_LIMIT {
+                return None;
+            }
+            let mut merged = BTreeMap::new();
+            for (a, selection_a) in &states {
+                for (b, selection_b) in &child_states {
+                    merged.entry(a + b).or_insert_with(|| join(selection_a, selection_b));
+                }
+            }
+            states = prune(merged, need);
+            if states.len() > EXACT_STATE_LIMIT {
+                return None;
+            }
+        }
+    }
+    if let Some(candidate) = candidates.get(&index) {
+        states.insert(candidate.size, Some(Rc::new(Picks::One(index))));
+        states = prune(states, need);
+    }
+    Some(states)
+}
+
+fn prune(mut states: BTreeMap<u64, Selection>, need: u64) -> BTreeMap<u64, Selection> {
+    let mut over = states.split_off(&need);
+    if let Some((total, selection)) = over.pop_first() {
+        states.insert(total, selection);
+    }
+    states
+}
+
+/// Repeatedly deletes the largest entry that still leaves something to free, and after each step
+/// considers finishing with the smallest single entry that covers the rest; keeps the best finish.
+fn greedy(candidates: &[Candidate], need: u64) -> Vec<usize> {
+    let mut chosen: HashSet<usize> = HashSet::new();
+    let mut blocked: HashSet<usize> = HashSet::new();
+    let mut total = 0;
+    let mut best: Option<(u64, Vec<usize>)> = None;
+    loop {
+        let remaining = need - total;
+        let available = || {
+            candidates.iter().filter(|candidate| {
+                !blocked.contains(&candidate.index) && !candidate.ancestors.iter().any(|ancestor| chosen.contains(ancestor))
+            })
+        };
+        if let Some(finish) = available().filter(|candidate| candidate.size >= remaining).min_by_key(|candidate| candidate.size) {
+            if best.as_ref().is_none_or(|(best_total, _)| total + finish.size < *best_total) {
+                let picks = chosen.iter().copied().chain([finish.index]).collect();
+                best = Some((total + finish.size, picks));
+            }
+        }
+        let Some(next) = available().filter(|candidate| candidate.size < remaining && candidate.size > 0).max_by_key(|candidate| candidate.size) else {
+            break;
+        };
+        chosen.insert(next.index);
+        blocked.insert(next.index);
+        blocked.extend(next.ancestors.iter().copied());
+        total += next.size;
+        if total == need {
+            return chosen.into_iter().collect();
+        }
+    }
+    best.map(|(_, picks)| picks).unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{interpret_instructions, parse_instructions};
+
+    fn filesystem(transcript: &str) -> FileSystem {
+        let lines = transcript.lines().map(|line| Ok(line.to_string())).collect();
+        interpret_instructions(parse_instructions(lines).unwrap()).unwrap()
+    }
+
+    const TREE: &str = "$ cd /\n$ ls\ndir a\ndir b\n7 top\n$ cd a\n$ ls\ndir c\n13 x\n4 y\n$ cd c\n$ ls\n9 z\n2 w\n$ cd /\n$ cd b\n$ ls\n6 u\n5 v\n";
+
+    /// the smallest total of non-nested, unprotected entries covering `need`, trying every subset
+    fn brute_force(filesystem: &FileSystem, need: u64, protected: &[Path]) -> Option<u64> {
+        let mut candidates = Vec::new();
+        collect_candidates(filesystem, filesystem.root, &mut Vec::new(), &mut Vec::new(), protected, &mut candidates);
+        (0u32..1 << candidates.len())
+            .filter(|subset| {
+                let chosen = |i: usize| subset & (1 << i) != 0;
+                (0..candidates.len()).filter(|i| chosen(*i)).all(|i| {
+                    !candidates.iter().enumerate().any(|(j, other)| chosen(j) && candidates[i].ancestors.contains(&other.index))
+                })
+            })
+            .map(|subset| (0..candidates.len()).filter(|i| subset & (1 << i) != 0).map(|i| candidates[i].size).sum::<u64>())
+            .filter(|total| *total >= need)
+            .min()
+    }
+
+    fn check_plan(plan: &Plan, need: u64, protected: &[Path]) {
+        assert!(plan.reclaimed >= need);
+        assert_eq!(plan.reclaimed, plan.deletions.iter().map(|deletion| deletion.size).sum::<u64>());
+        for deletion in &plan.deletions {
+            assert!(!is_protected(&deletion.path, protected), "{:?} touches a protected path", deletion.path);
+            let nested = plan.deletions.iter().any(|other| other.path != deletion.path && deletion.path.starts_with(&other.path));
+            assert!(!nested, "{:?} is inside another deletion", deletion.path);
+        }
+    }
+
+    #[test]
+    fn exact_matches_brute_force() {
+        let filesystem = filesystem(TREE);
+        for need in 1..=filesystem.total_size(filesystem.root) {
+            let exact = plan(&filesystem, need, &[], Solver::Exact).unwrap();
+            check_plan(&exact, need, &[]);
+            assert!(exact.exact);
+            assert_eq!(Some(exact.reclaimed), brute_force(&filesystem, need, &[]), "need {}", need);
+            let greedy = plan(&filesystem, need, &[], Solver::Greedy).unwrap();
+            check_plan(&greedy, need, &[]);
+            assert!(greedy.reclaimed >= exact.reclaimed);
+        }
+    }
+
+    #[test]
+    fn protected_paths_are_kept() {
+        let filesystem = filesystem(TREE);
+        let protected = vec![vec!["a".to_string(), "c".to_string(), "z".to_string()]];
+        // only b, top, a/x, a/y and a/c/w remain: 11 + 7 + 13 + 4 + 2
+        for need in 1..=37 {
+            for solver in [Solver::Exact, Solver::Greedy] {
+                let plan = plan(&filesystem, need, &protected, solver).unwrap();
+                check_plan(&plan, need, &protected);
+                if solver == Solver::Exact {
+                    assert_eq!(Some(plan.reclaimed), brute_force(&filesystem, need, &protected), "need {}", need);
+                }
+            }
+        }
+        assert!(plan(&filesystem, 38, &protected, Solver::Auto).is_err());
+    }
+
+    #[test]
+    fn ties_are_broken_the_same_way_every_time() {
+        let transcript = "$ cd /\n$ ls\ndir a\ndir b\ndir c\ndir d\n$ cd a\n$ ls\n10 f\n$ cd ../b\n$ ls\n10 f\n$ cd ../c\n$ ls\n10 f\n$ cd ../d\n$ ls\n10 f\n";
+        let picks = |filesystem: &FileSystem| plan(filesystem, 10, &[], Solver::Exact).unwrap().deletions[0].path.clone();
+        let first = picks(&filesystem(transcript));
+        for _ in 0..20 {
+            assert_eq!(picks(&filesystem(transcript)), first);
+        }
+    }
+}
@@ -0,0 +1,168 @@
+use std::fs;
+use std::fs::OpenOptions;
+use std::io;
+use std::io::{ErrorKind, Read, Write};
+use std::path::{Path, PathBuf};
+use serde_json::{json, Value};
+use tar::{Builder, EntryType, Header};
+use crate::{FileSystem, FileSystemEntry};
+
+/// Rejects names that would escape the target directory or can't be a single path component
+fn checked_name(name: &str) -> io::Result<&str> {
+    if name.is_empty() || name == "." || name == ".." || name.contains('/') || name.contains('\0') {
+        return Err(io::Error::new(ErrorKind::InvalidData, format!("can't export an entry named {:?}", name)));
+    }
+    Ok(name)
+}
+
+/// Recreates the filesystem under `target`, each file becoming a sparse file of its recorded size.
+/// Existing files are never overwritten.
+pub fn materialize(filesystem: &FileSystem, target: &Path) -> io::Result<()> {
+    fs::create_dir_all(target)?;
+    materialize_directory(filesystem, filesystem.root, target)
+}
+
+fn materialize_directory(filesystem: &FileSystem, directory: usize, path: &Path) -> io::Result<()> {
+    for (name, index) in filesystem.sorted_children(directory) {
+        let child = path.join(checked_name(name)?);
+        match &filesystem.entries[index] {
+            FileSystemEntry::DirectoryEntry(_) => {
+                if !child.is_dir() {
+                    fs::create_dir(&child)?;
+                }
+                materialize_directory(filesystem, index, &child)?;
+            }
+            FileSystemEntry::FileEntry(file) => {
+                let created = OpenOptions::new()
+                    .write(true)
+                    .create_new(true)
+                    .open(&child)
+                    .map_err(|e| io::Error::new(e.kind(), format!("{}: {}", child.display(), e)))?;
+                // extending with set_len leaves a hole instead of writing zeros
+                created.set_len(file.size)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Writes the filesystem as a tar archive; file contents are zeros of the recorded size
+pub fn write_tar<W: Write>(filesystem: &FileSystem, output: W) -> io::Result<()> {
+    let mut builder = Builder::new(output);
+    append_directory(filesystem, filesystem.root, &PathBuf::new(), &mut builder)?;
+    builder.into_inner()?.flush()
+}
+
+fn append_directory<W: Write>(filesystem: &FileSystem, directory: usize, path: &Path, builder: &mut Builder<W>) -> io::Result<()> {
+    for (name, index) in filesystem.sorted_children(directory) {
+        let child = path.join(checked_name(name)?);
+        let mut header = Header::new_gnu();
+        header.set_mtime(0);
+        match &filesystem.entries[index] {
+            FileSystemEntry::DirectoryEntry(_) => {
+                header.set_entry_type(EntryType::Directory);
+                header.set_mode(0o755);
+                header.set_size(0);
+                builder.append_data(&mut header, &child, io::empty())?;
+                append_directory(filesystem, index, &child, builder)?;
+            }
+            FileSystemEntry::FileEntry(file) => {
+                header.set_entry_type(EntryType::Regular);
+                header.set_mode(0o644);
+                header.set_size(file.size);
+                builder.append_data(&mut header, &child, io::repeat(0).take(file.size))?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Builds the nested JSON for one entry: its slab index and its parent's, its own size (for a
+/// directory, the files directly inside it) and its cumulative size
+fn json_entry(filesystem: &FileSystem, index: usize, name: &str, parent: Option<usize>) -> Value {
+    match &filesystem.entries[index] {
+        FileSystemEntry::FileEntry(file) => json!({
+            "name": name,
+            "index": index,
+            "parent": parent,
+            "type": "file",
+            "size": file.size,
+            "cumulative_size": file.size,
+        }),
+        FileSystemEntry::DirectoryEntry(directory) => {
+            let children: Vec<Value> = filesystem
+                .sorted_children(index)
+                .into_iter()
+                .map(|(child_name, child)| json_entry(filesystem, child, child_name, Some(index)))
+                .collect();
+            let files: u64 = filesystem
+                .sorted_children(index)
+                .into_iter()
+                .filter_map(|(_, child)| match &filesystem.entries[child] {
+                    FileSystemEntry::FileEntry(file) => Some(file.size),
+                    FileSystemEntry::DirectoryEntry(_) => None,
+                })
+                .sum();
+            json!({
+                "name": name,
+                "index": index,
+                "parent": parent,
+                "type": "directory",
+                "size": files,
+                "cumulative_size": directory.size,
+                "children": children,
+            })
+        }
+    }
+}
+
+/// Writes the whole tree as one nested JSON object rooted at `/`
+pub fn write_json<W: Write>(filesystem: &FileSystem, mut output: W) -> io::Result<()> {
+    let root = json_entry(filesystem, filesystem.root, "/", None);
+    serde_json::to_writer_pretty(&mut output, &root)?;
+    writeln!(output)?;
+    output.flush()
+}
+
+fn dot_escape(text: &str) -> String {
+    text.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+/// an HSV fill from pale to saturated red as `size` grows toward `total`, on a log scale so small
+/// entries stay distinguishable
+fn dot_color(size: u64, total: u64, saturation: f64) -> String {
+    let scale = if total <= 1 { 0.0 } else { (size as f64).ln_1p() / (total as f64).ln_1p() };
+    format!("0.000 {:.3} 1.000", scale * saturation)
+}
+
+/// Writes a Graphviz digraph with one cluster per directory and node colours scaled by size;
+/// render it with `dot -Tsvg`
+pub fn write_dot<W: Write>(filesystem: &FileSystem, mut output: W) -> io::Result<()> {
+    let total = filesystem.total_size(filesystem.root);
+    writeln!(output, "digraph filesystem {{")?;
+    writeln!(output, "    rankdir=LR;")?;
+    writeln!(output, "    node [shape=box, style=filled, fontname=\"monospace\"];")?;
+    dot_directory(filesystem, filesystem.root, "/", total, 1, &mut output)?;
+    writeln!(output, "}}")?;
+    output.flush()
+}
+
+fn dot_directory<W: Write>(filesystem: &FileSystem, directory: usize, name: &str, total: u64, depth: usize, output: &mut W) -> io::Result<()> {
+    let indent = "    ".repeat(depth);
+    let size = filesystem.total_size(directory);
+    writeln!(output, "{}subgraph cluster_{} {{", indent, directory)?;
+    writeln!(output, "{}    label=\"{} ({})\";", indent, dot_escape(name), size)?;
+    writeln!(output, "{}    style=filled; fillcolor=\"{}\";", indent, dot_color(size, total, 0.25))?;
+    writeln!(output, "{}    n{} [label=\"{}\\n{}\", fillcolor=\"{}\"];", indent, directory, dot_escape(name), size, dot_color(size, total, 1.0))?;
+    for (child_name, child) in filesystem.sorted_children(directory) {
+        match &filesystem.entries[child] {
+            FileSystemEntry::FileEntry(file) => {
+                let color = dot_color(file.size, total, 1.0);
+                writeln!(output, "{}    n{} [label=\"{}\\n{}\", shape=note, fillcolor=\"{}\"];", indent, child, dot_escape(child_name), file.size, color)?;
+            }
+            FileSystemEntry::DirectoryEntry(_) => dot_directory(filesystem, child, &format!("{}/", child_name), total, depth + 1, output)?,
+        }
+        writeln!(output, "{}    n{} -> n{};", indent, directory, child)?;
+    }
+    writeln!(output, "{}}}", indent)
+}
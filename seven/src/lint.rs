@@ -0,0 +1,296 @@
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::mem::discriminant;
+use crate::{parse_cd_target, Path, Segment, CD_REGEX, LS_CHILD_DIR_OUTPUT_REGEX, LS_FILE_SIZE_OUTPUT_REGEX, LS_REGEX};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Severity {
+    /// the loader accepts the line but the transcript is suspicious
+    Warning,
+    /// the loader rejects the transcript because of this line
+    Error,
+}
+
+/// A problem found at a 1-based line of the transcript
+#[derive(Clone, Debug)]
+pub struct Diagnostic {
+    pub line: usize,
+    pub severity: Severity,
+    pub message: String,
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let severity = match self.severity {
+            Severity::Warning => "warning",
+            Severity::Error => "error",
+        };
+        write!(f, "{}: {}: {}", self.line, severity, self.message)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Kind {
+    File(u64),
+    Directory,
+}
+
+/// what one `ls` showed: each name with its kind and the line it was printed on
+struct Listing {
+    line: usize,
+    entries: BTreeMap<String, (Kind, usize)>,
+}
+
+#[derive(Default)]
+struct Linter {
+    diagnostics: Vec<Diagnostic>,
+    /// the most recent listing of every listed directory
+    listings: HashMap<Path, Listing>,
+    /// every directory seen in an `ls`, with the line that first showed it
+    directories: BTreeMap<Path, usize>,
+    cwd: Path,
+    /// the listing being read, with the directory it belongs to
+    current: Option<(Path, Listing)>,
+}
+
+fn display(path: &[String]) -> String {
+    format!("/{}", path.join("/"))
+}
+
+fn child(path: &[String], name: &str) -> Path {
+    let mut path = path.to_vec();
+    path.push(name.to_string());
+    path
+}
+
+impl Linter {
+    fn report(&mut self, line: usize, severity: Severity, message: String) {
+        self.diagnostics.push(Diagnostic { line, severity, message });
+    }
+
+    fn cd(&mut self, line: usize, dir: &str) {
+        let (absolute, segments) = match parse_cd_target(dir) {
+            Ok(target) => target,
+            Err(e) => return self.report(line, Severity::Error, e.to_string()),
+        };
+        if absolute {
+            self.cwd.clear();
+        }
+        for segment in segments {
+            match segment {
+                Segment::Current => {}
+                Segment::Parent => {
+                    if self.cwd.pop().is_none() {
+                        self.report(line, Severity::Error, "cd ..: already at /".to_string());
+                    }
+                }
+                Segment::Name(name) => {
+                    let target = child(&self.cwd, &name);
+                    // like the loader, a directory shown by any earlier ls can be entered
+                    if !self.directories.contains_key(&target) {
+                        let shown = self.listings.get(&self.cwd).map(|listing| (listing.line, listing.entries.get(&name).map(|(kind, _)| *kind)));
+                        let message = match shown {
+                            None => format!("cd into {} before {} was listed", display(&target), display(&self.cwd)),
+                            Some((ls_line, Some(Kind::File(_)))) => format!("cd into {}, which the ls at line {} showed as a file", display(&target), ls_line),
+                            Some((ls_line, _)) => format!("cd into {}, which the ls at line {} didn't show", display(&target), ls_line),
+                        };
+                        self.report(line, Severity::Error, message);
+                    }
+                    self.cwd = target;
+                }
+            }
+        }
+    }
+
+    fn output(&mut self, line: usize, text: &str) {
+        let Some((directory, listing)) = self.current.as_mut() else {
+            return self.report(line, Severity::Warning, format!("output outside of an ls is ignored: {:?}", text));
+        };
+        let (name, kind) = if let Some(captures) = LS_FILE_SIZE_OUTPUT_REGEX.captures(text) {
+            match captures[1].parse() {
+                Ok(size) => (captures[2].to_string(), Kind::File(size)),
+                Err(_) => return self.report(line, Severity::Error, format!("file size {} is out of range", &captures[1])),
+            }
+        } else if let Some(captures) = LS_CHILD_DIR_OUTPUT_REGEX.captures(text) {
+            (captures[1].to_string(), Kind::Directory)
+        } else if text.starts_with(|c: char| c.is_ascii_digit()) || text.starts_with("dir") {
+            return self.report(line, Severity::Error, format!("malformed ls output: {:?}", text));
+        } else {
+            return self.report(line, Severity::Warning, format!("unrecognized ls output is ignored: {:?}", text));
+        };
+        let path = child(directory, &name);
+        if let Some(&(previous, previous_line)) = listing.entries.get(&name) {
+            // keep whichever entry the loader keeps: the later size of a file, the first type of a name
+            let message = match (previous, kind) {
+                (Kind::File(before), Kind::File(after)) if before != after => {
+                    listing.entries.insert(name, (kind, line));
+                    format!("{} is listed again with size {}, line {} gave {}; the later size is used", display(&path), after, previous_line, before)
+                }
+                _ if discriminant(&previous) != discriminant(&kind) => {
+                    format!("{} is listed again with a different type (first at line {}); the first is used", display(&path), previous_line)
+                }
+                _ => format!("{} is listed again (first at line {})", display(&path), previous_line),
+            };
+            return self.report(line, Severity::Warning, message);
+        }
+        listing.entries.insert(name, (kind, line));
+        if kind == Kind::Directory {
+            self.directories.entry(path).or_insert(line);
+        }
+    }
+
+    /// compares a finished listing with the previous listing of the same directory
+    fn finish_listing(&mut self) {
+        let Some((directory, mut listing)) = self.current.take() else { return };
+        if let Some(previous) = self.listings.get(&directory) {
+            let mut messages = Vec::new();
+            for (name, (kind, line)) in &listing.entries {
+                let path = display(&child(&directory, name));
+                match (previous.entries.get(name), kind) {
+                    (None, _) => messages.push((listing.line, format!("{} wasn't in the ls at line {}", path, previous.line))),
+                    (Some((Kind::File(before), _)), Kind::File(after)) if before != after => {
+                        messages.push((*line, format!("{} changed size from {} to {} since line {}", path, before, after, previous.line)));
+                    }
+                    (Some((before, _)), after) if before != after => {
+                        messages.push((*line, format!("{} changed between file and directory since line {}", path, previous.line)));
+                    }
+                    _ => {}
+                }
+            }
+            for name in previous.entries.keys().filter(|name| !listing.entries.contains_key(*name)) {
+                let message = format!("{} from the ls at line {} is missing", display(&child(&directory, name)), previous.line);
+                messages.push((listing.line, message));
+            }
+            // like the loader, an entry that changed between file and directory keeps its first type
+            for (name, entry) in listing.entries.iter_mut() {
+                if let Some(before) = previous.entries.get(name).filter(|before| discriminant(&before.0) != discriminant(&entry.0)) {
+                    *entry = *before;
+                }
+            }
+            for (line, message) in messages {
+                self.report(line, Severity::Warning, message);
+            }
+        }
+        self.listings.insert(directory, listing);
+    }
+}
+
+/// Checks a transcript for lines the loader ignores or rejects, listings that disagree with each
+/// other, `cd`s into directories no `ls` showed, and directories whose size is unknown because they
+/// were never listed. Diagnostics come back sorted by line; with `strict` every one is an error.
+pub fn lint(lines: &[String], strict: bool) -> Vec<Diagnostic> {
+    let mut linter = Linter::default();
+    linter.directories.insert(Vec::new(), 0);
+    for (i, text) in lines.iter().enumerate() {
+        let line = i + 1;
+        if text.starts_with('$') {
+            linter.finish_listing();
+            if let Some(captures) = CD_REGEX.captures(text) {
+                linter.cd(line, &captures[1]);
+            } else if LS_REGEX.is_match(text) {
+                let listing = Listing { line, entries: BTreeMap::new() };
+                linter.current = Some((linter.cwd.clone(), listing));
+            } else {
+                linter.report(line, Severity::Error, format!("unrecognized command: {:?}", text));
+            }
+        } else {
+            linter.output(line, text);
+        }
+    }
+    linter.finish_listing();
+
+    let unlisted: Vec<(Path, usize)> = linter
+        .directories
+        .iter()
+        .filter(|(path, _)| !linter.listings.contains_key(*path))
+        .map(|(path, line)| (path.clone(), *line))
+        .collect();
+    for (path, line) in unlisted {
+        let message = format!("contents of {} were never listed, so its size is unknown", display(&path));
+        linter.report(line.max(1), Severity::Warning, message);
+    }
+    linter.diagnostics.sort_by_key(|diagnostic| diagnostic.line);
+    if strict {
+        linter.diagnostics.iter_mut().for_each(|diagnostic| diagnostic.severity = Severity::Error);
+    }
+    linter.diagnostics
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lint_text(transcript: &str, strict: bool) -> Vec<(usize, Severity, String)> {
+        let lines: Vec<String> = transcript.lines().map(str::to_string).collect();
+        lint(&lines, strict).into_iter().map(|diagnostic| (diagnostic.line, diagnostic.severity, diagnostic.message)).collect()
+    }
+
+    /// the single diagnostic for `transcript`, which must contain `message`
+    fn only(transcript: &str, line: usize, severity: Severity, message: &str) {
+        let diagnostics = lint_text(transcript, false);
+        assert_eq!(diagnostics.len(), 1, "{:?}", diagnostics);
+        let (actual_line, actual_severity, actual_message) = &diagnostics[0];
+        assert_eq!((*actual_line, *actual_severity), (line, severity), "{:?}", diagnostics);
+        assert!(actual_message.contains(message), "{:?} doesn't mention {:?}", actual_message, message);
+    }
+
+    #[test]
+    fn clean_transcript() {
+        assert!(lint_text("$ cd /\n$ ls\ndir a\n5 f\n$ cd a\n$ ls\n7 g\n$ cd ..\n$ ls\ndir a\n5 f\n", true).is_empty());
+    }
+
+    #[test]
+    fn unrecognized_lines() {
+        only("$ ls\n$ pwd\n", 2, Severity::Error, "unrecognized command");
+        only("$ ls\n12x\n", 2, Severity::Error, "malformed ls output");
+        only("$ ls\n99999999999999999999999 f\n", 2, Severity::Error, "out of range");
+        only("$ ls\ntotal: nothing\n", 2, Severity::Warning, "unrecognized ls output");
+        only("$ ls\n$ cd /\n5 f\n", 3, Severity::Warning, "output outside of an ls");
+    }
+
+    #[test]
+    fn repeated_names_in_one_listing() {
+        only("$ ls\n5 a\n5 a\n", 3, Severity::Warning, "/a is listed again (first at line 2)");
+        only("$ ls\n5 a\n7 a\n", 3, Severity::Warning, "/a is listed again with size 7, line 2 gave 5; the later size is used");
+        only("$ ls\n5 a\ndir a\n", 3, Severity::Warning, "/a is listed again with a different type");
+    }
+
+    #[test]
+    fn listings_compared_with_the_size_the_loader_kept() {
+        // the loader uses 7, so the second listing changes the size from 7, not from 5
+        let diagnostics = lint_text("$ ls\n5 a\n7 a\n$ ls\n9 a\n", false);
+        assert_eq!(diagnostics.len(), 2, "{:?}", diagnostics);
+        assert!(diagnostics[1].2.contains("changed size from 7 to 9 since line 1"), "{:?}", diagnostics);
+    }
+
+    #[test]
+    fn listings_that_disagree() {
+        only("$ ls\n5 a\n$ ls\n5 a\n6 b\n", 3, Severity::Warning, "/b wasn't in the ls at line 1");
+        only("$ ls\n5 a\n6 b\n$ ls\n5 a\n", 4, Severity::Warning, "/b from the ls at line 1 is missing");
+        only("$ ls\n5 a\n$ ls\n6 a\n", 4, Severity::Warning, "changed size from 5 to 6");
+        let diagnostics = lint_text("$ ls\n5 a\n$ ls\ndir a\n", false);
+        assert!(diagnostics.iter().any(|(line, _, message)| *line == 4 && message.contains("changed between file and directory")), "{:?}", diagnostics);
+    }
+
+    #[test]
+    fn bad_cds() {
+        only("$ cd a\n$ ls\n$ cd /\n$ ls\n", 1, Severity::Error, "cd into /a before / was listed");
+        only("$ ls\n5 a\n$ cd a\n", 3, Severity::Error, "showed as a file");
+        only("$ ls\n5 a\n$ cd b\n", 3, Severity::Error, "didn't show");
+        only("$ ls\n$ cd ..\n", 2, Severity::Error, "already at /");
+    }
+
+    #[test]
+    fn unlisted_directories() {
+        only("$ ls\ndir a\n", 2, Severity::Warning, "contents of /a were never listed");
+    }
+
+    #[test]
+    fn strict_turns_warnings_into_errors() {
+        let transcript = "$ ls\ndir a\n5 f\n5 f\n";
+        assert!(lint_text(transcript, false).iter().all(|(_, severity, _)| *severity == Severity::Warning));
+        let strict = lint_text(transcript, true);
+        assert_eq!(strict.len(), 2);
+        assert!(strict.iter().all(|(_, severity, _)| *severity == Severity::Error));
+    }
+}
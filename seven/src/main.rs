@@ -1,4 +1,4 @@
-use std::collections::HashMap;
+use std::collections::{BTreeSet, HashMap};
 use std::error::Error;
 use std::fs::File;
 use std::io;
@@ -8,22 +8,34 @@ use lazy_static::lazy_static;
 use regex::Regex;
 use slab::Slab;
 
-const CD_REGEX_STR: &str = r"\$ cd ([\w/\.]+)";
-const LS_REGEX_STR: &str = r"\$ ls";
-const LS_FILE_SIZE_OUTPUT: &str = r"(\d+) (\w+)";
-const LS_CHILD_DIR_OUTPUT: &str = r"dir (\w+)";
+mod cleanup;
+mod export;
+mod lint;
+mod shell;
+mod transcript;
+mod treemap;
+
+const CD_REGEX_STR: &str = r"^\$ cd (.+)$";
+const LS_REGEX_STR: &str = r"^\$ ls$";
+const LS_FILE_SIZE_OUTPUT: &str = r"^(\d+) (.+)$";
+const LS_CHILD_DIR_OUTPUT: &str = r"^dir (.+)$";
 lazy_static! {
-    static ref cd_regex: Regex = Regex::new(CD_REGEX_STR).unwrap();
+    static ref CD_REGEX: Regex = Regex::new(CD_REGEX_STR).unwrap();
 }
 lazy_static! {
-    static ref ls_regex: Regex = Regex::new(LS_REGEX_STR).unwrap();
+    static ref LS_REGEX: Regex = Regex::new(LS_REGEX_STR).unwrap();
 }
 lazy_static! {
-    static ref ls_file_size_output_regex: Regex = Regex::new(LS_FILE_SIZE_OUTPUT).unwrap();
+    static ref LS_FILE_SIZE_OUTPUT_REGEX: Regex = Regex::new(LS_FILE_SIZE_OUTPUT).unwrap();
 }
 lazy_static! {
-    static ref ls_child_dir_output_regex: Regex = Regex::new(LS_CHILD_DIR_OUTPUT).unwrap();
+    static ref LS_CHILD_DIR_OUTPUT_REGEX: Regex = Regex::new(LS_CHILD_DIR_OUTPUT).unwrap();
 }
+
+const TOTAL_DISK_SPACE: u64 = 70_000_000;
+const REQUIRED_FREE_SPACE: u64 = 30_000_000;
+const SMALL_DIRECTORY_LIMIT: u64 = 100_000;
+
 #[derive(Clone, Debug)]
 struct FileSize {
     name: String,
@@ -39,11 +51,11 @@ impl FromStr for FileSize {
     type Err = io::Error;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        if let Some(captures) = ls_file_size_output_regex.captures(s) {
+        if let Some(captures) = LS_FILE_SIZE_OUTPUT_REGEX.captures(s) {
             Ok(
                 FileSize {
                     name: captures[2].to_string(),
-                    size: captures[1].parse().unwrap(),
+                    size: captures[1].parse().map_err(|_| io::Error::new(ErrorKind::InvalidData, "file size out of range"))?,
                 }
             )
         } else {
@@ -56,7 +68,7 @@ impl FromStr for ChildDir {
     type Err = io::Error;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        if let Some(captures) = ls_child_dir_output_regex.captures(s) {
+        if let Some(captures) = LS_CHILD_DIR_OUTPUT_REGEX.captures(s) {
             Ok(
                 ChildDir {
                     name: captures[1].to_string(),
@@ -81,11 +93,11 @@ impl FromStr for Instruction {
     type Err = io::Error;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        if let Some(cd) = cd_regex.captures(s) {
+        if let Some(cd) = CD_REGEX.captures(s) {
             Ok(
                 Instruction::Cd { dir: cd[1].to_string() }
             )
-        } else if ls_regex.is_match(s) {
+        } else if LS_REGEX.is_match(s) {
             Ok(
                 Instruction::Ls {
                     output: Vec::new(),
@@ -101,6 +113,7 @@ impl FromStr for Instruction {
 struct DirectoryEntry {
     children: HashMap<String, usize>,
     parent: Option<usize>,
+    /// bytes in all files below this directory, kept up to date as files are inserted
     size: u64,
 }
 
@@ -128,6 +141,8 @@ enum FileSystemEntry {
 struct FileSystem {
     entries: Slab<FileSystemEntry>,
     index: HashMap<Vec<String>, usize>,
+    /// every directory as (cumulative size, slab index), ordered for size queries
+    by_size: BTreeSet<(u64, usize)>,
     root: usize,
 }
 
@@ -136,32 +151,132 @@ const FILESYSTEM_INITIAL_CAPACITY: usize = 1024;
 type Path = Vec<String>;
 
 impl FileSystem {
-    fn new(root: usize) -> FileSystem {
-        let entries = Slab::with_capacity(FILESYSTEM_INITIAL_CAPACITY);
-        let index = HashMap::with_capacity(FILESYSTEM_INITIAL_CAPACITY);
+    fn new() -> FileSystem {
+        let mut entries = Slab::with_capacity(FILESYSTEM_INITIAL_CAPACITY);
+        let mut index = HashMap::with_capacity(FILESYSTEM_INITIAL_CAPACITY);
+        let root = entries.insert(FileSystemEntry::DirectoryEntry(DirectoryEntry::new(None)));
+        index.insert(Vec::new(), root);
+        let by_size = BTreeSet::from([(0, root)]);
         FileSystem {
             root,
             entries,
             index,
+            by_size,
         }
     }
-    fn root(&self) -> Option<&FileSystemEntry> {
-        self.entries.get(self.root)
-    }
-    fn get(&self, path: Path) -> Option<(usize, &FileSystemEntry)> {
-        let result = self.index.get(&path)?;
+    fn get(&self, path: &Path) -> Option<(usize, &FileSystemEntry)> {
+        let result = self.index.get(path)?;
         let entry = self.entries.get(*result)?;
 
         Some((*result, entry))
     }
-    fn insert_dir(&mut self, path: Path, parent: Option<usize>) -> usize {
-        let new_entry = FileSystemEntry::DirectoryEntry(DirectoryEntry::new(parent));
-        let index = self.entries.insert(new_entry);
+    /// Inserts an entry below `parent`. When the parent already has a child with that name the
+    /// existing entry is kept, so listing a directory twice doesn't count its files twice; a file
+    /// listed again with a different size takes the new size.
+    fn insert(&mut self, path: Path, parent: usize, entry: FileSystemEntry) -> usize {
+        if let Some((index, existing)) = self.get(&path) {
+            if let (FileSystemEntry::FileEntry(old), FileSystemEntry::FileEntry(new)) = (existing, &entry) {
+                let (old_size, new_size) = (old.size, new.size);
+                if old_size != new_size {
+                    self.entries[index] = entry;
+                    self.propagate(parent, old_size, new_size);
+                }
+            }
+            return index;
+        }
+        let name = path.last().expect("only the root has an empty path").clone();
+        let file_size = match &entry {
+            FileSystemEntry::FileEntry(file) => Some(file.size),
+            FileSystemEntry::DirectoryEntry(_) => None,
+        };
+        let index = self.entries.insert(entry);
+        if let Some(FileSystemEntry::DirectoryEntry(directory)) = self.entries.get_mut(parent) {
+            directory.children.insert(name, index);
+        }
+        match file_size {
+            Some(size) => self.propagate(parent, 0, size),
+            None => {
+                self.by_size.insert((0, index));
+            }
+        }
         self.index.insert(path, index);
         index
     }
+    /// replaces `old` bytes with `new` bytes in a directory and all of its ancestors
+    fn propagate(&mut self, directory: usize, old: u64, new: u64) {
+        let mut current = Some(directory);
+        while let Some(index) = current {
+            let Some(FileSystemEntry::DirectoryEntry(directory)) = self.entries.get_mut(index) else { break };
+            self.by_size.remove(&(directory.size, index));
+            directory.size = directory.size - old + new;
+            self.by_size.insert((directory.size, index));
+            current = directory.parent;
+        }
+    }
+    /// index of the entry called `name` directly inside a directory
+    fn child(&self, directory: usize, name: &str) -> Option<usize> {
+        match self.entries.get(directory)? {
+            FileSystemEntry::DirectoryEntry(directory) => directory.children.get(name).copied(),
+            FileSystemEntry::FileEntry(_) => None,
+        }
+    }
+    /// children of a directory sorted by name
+    fn sorted_children(&self, directory: usize) -> Vec<(&String, usize)> {
+        let mut children: Vec<(&String, usize)> = match &self.entries[directory] {
+            FileSystemEntry::DirectoryEntry(directory) => directory.children.iter().map(|(name, index)| (name, *index)).collect(),
+            FileSystemEntry::FileEntry(_) => Vec::new(),
+        };
+        children.sort();
+        children
+    }
+    fn insert_dir(&mut self, path: Path, parent: usize) -> usize {
+        self.insert(path, parent, FileSystemEntry::DirectoryEntry(DirectoryEntry::new(Some(parent))))
+    }
+    fn insert_file(&mut self, path: Path, parent: usize, size: u64) -> usize {
+        self.insert(path, parent, FileSystemEntry::FileEntry(FileEntry { size }))
+    }
+    /// size of a file, or of everything below a directory
+    fn total_size(&self, index: usize) -> u64 {
+        match &self.entries[index] {
+            FileSystemEntry::FileEntry(file) => file.size,
+            FileSystemEntry::DirectoryEntry(directory) => directory.size,
+        }
+    }
+    /// total size of the entry at `path`
+    fn subtree_size(&self, path: &Path) -> Option<u64> {
+        let (index, _) = self.get(path)?;
+        Some(self.total_size(index))
+    }
+    /// the `n` largest directories, largest first, as (slab index, size)
+    fn largest_directories(&self, n: usize) -> Vec<(usize, u64)> {
+        self.by_size.iter().rev().take(n).map(|(size, index)| (*index, *size)).collect()
+    }
+    /// directories of at most `limit` bytes, smallest first
+    fn directories_at_most(&self, limit: u64) -> impl Iterator<Item = (usize, u64)> + '_ {
+        self.by_size.range(..=(limit, usize::MAX)).map(|(size, index)| (*index, *size))
+    }
+    /// directories of at least `limit` bytes, smallest first
+    fn directories_at_least(&self, limit: u64) -> impl Iterator<Item = (usize, u64)> + '_ {
+        self.by_size.range((limit, 0)..).map(|(size, index)| (*index, *size))
+    }
+    /// rebuilds the path of a directory by following its parent links
+    fn path_of(&self, index: usize) -> Path {
+        let mut path = Vec::new();
+        let mut current = index;
+        while let Some(FileSystemEntry::DirectoryEntry(DirectoryEntry { parent: Some(parent), .. })) = self.entries.get(current) {
+            if let Some(FileSystemEntry::DirectoryEntry(directory)) = self.entries.get(*parent) {
+                if let Some((name, _)) = directory.children.iter().find(|(_, child)| **child == current) {
+                    path.push(name.clone());
+                }
+            }
+            current = *parent;
+        }
+        path.reverse();
+        path
+    }
 }
 
+#[derive(Clone)]
 struct FileSystemCursor {
     stack: Vec<String>,
     root: usize,
@@ -180,7 +295,6 @@ impl FileSystemCursor {
     fn pop(&mut self) {
         self.stack.pop();
         self.parent.pop();
-        ()
     }
     fn push(&mut self, entry: String, parent: usize) {
         self.stack.push(entry);
@@ -190,83 +304,309 @@ impl FileSystemCursor {
         self.stack.clear();
         self.parent.clear();
     }
+    /// index of the directory the cursor is in
+    fn current(&self) -> usize {
+        self.parent.last().copied().unwrap_or(self.root)
+    }
 
     fn path(&self) -> Vec<String> {
-        let mut path = self.stack.clone();
+        self.stack.clone()
+    }
+    fn child_path(&self, name: &str) -> Vec<String> {
+        let mut path = self.path();
+        path.push(name.to_string());
         path
     }
+
+    /// Changes directory like a shell would: `dir` may be absolute, may contain several segments,
+    /// `.` and `..`, and quoted names. Every directory on the way must already be known from an `ls`
+    /// of its parent; on error the cursor doesn't move.
+    fn cd(&mut self, filesystem: &FileSystem, dir: &str) -> io::Result<()> {
+        let (absolute, segments) = parse_cd_target(dir)?;
+        let mut next = self.clone();
+        if absolute {
+            next.root();
+        }
+        for segment in segments {
+            match segment {
+                Segment::Current => {}
+                Segment::Parent => {
+                    if next.stack.is_empty() {
+                        return Err(io::Error::new(ErrorKind::InvalidInput, format!("cd {}: can't go above /", dir)));
+                    }
+                    next.pop();
+                }
+                Segment::Name(name) => {
+                    let location = format!("/{}", next.stack.join("/"));
+                    let index = match filesystem.child(next.current(), &name) {
+                        Some(index) if matches!(filesystem.entries[index], FileSystemEntry::DirectoryEntry(_)) => index,
+                        Some(_) => {
+                            let message = format!("cd {}: {} in {} is a file", dir, name, location);
+                            return Err(io::Error::new(ErrorKind::InvalidInput, message));
+                        }
+                        None => {
+                            let message = format!("cd {}: no directory {} was listed in {}", dir, name, location);
+                            return Err(io::Error::new(ErrorKind::NotFound, message));
+                        }
+                    };
+                    next.push(name, index);
+                }
+            }
+        }
+        *self = next;
+        Ok(())
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum Segment {
+    Current,
+    Parent,
+    Name(String),
+}
+
+/// Splits a `cd` argument into segments the way a shell would: single or double quotes keep spaces
+/// and other characters together and a backslash escapes the next character, while `/` always
+/// separates segments. Returns whether the path is absolute.
+fn parse_cd_target(dir: &str) -> io::Result<(bool, Vec<Segment>)> {
+    let mut segments = Vec::new();
+    let mut current = String::new();
+    let mut quote: Option<char> = None;
+    let mut absolute = false;
+    let mut chars = dir.trim().chars();
+    let finish = |current: &mut String, segments: &mut Vec<Segment>| match current.as_str() {
+        "" => {}
+        "." => segments.push(Segment::Current),
+        ".." => segments.push(Segment::Parent),
+        _ => segments.push(Segment::Name(std::mem::take(current))),
+    };
+    while let Some(c) = chars.next() {
+        match (c, quote) {
+            ('\\', _) => match chars.next() {
+                Some(escaped) => current.push(escaped),
+                None => return Err(io::Error::new(ErrorKind::InvalidData, format!("cd {}: trailing backslash", dir))),
+            },
+            ('"', None) | ('\'', None) => quote = Some(c),
+            (c, Some(open)) if c == open => quote = None,
+            ('/', _) => {
+                if segments.is_empty() && current.is_empty() {
+                    absolute = true;
+                }
+                finish(&mut current, &mut segments);
+                current.clear();
+            }
+            (c, _) => current.push(c),
+        }
+    }
+    if quote.is_some() {
+        return Err(io::Error::new(ErrorKind::InvalidData, format!("cd {}: unterminated quote", dir)));
+    }
+    finish(&mut current, &mut segments);
+    Ok((absolute, segments))
 }
 
+const DEFAULT_TRANSCRIPT: &str = "./seven/input.txt";
+
 fn main() -> Result<(), Box<dyn Error>> {
-    let file = File::open("./seven/input.txt")?;
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let transcript_path = |rest: &[String]| {
+        rest.first().filter(|arg| !arg.starts_with("--")).map_or(DEFAULT_TRANSCRIPT, |path| path.as_str()).to_string()
+    };
+    match args.first().map(|arg| arg.as_str()) {
+        Some("shell") => {
+            let file_system = load(&transcript_path(&args[1..]))?;
+            shell::run(&file_system, io::stdin().lock(), io::stdout().lock())?
+        }
+        Some("tar") => {
+            let file_system = load(&transcript_path(&args[1..]))?;
+            export::write_tar(&file_system, io::BufWriter::new(io::stdout().lock()))?
+        }
+        Some("json") => export::write_json(&load(&transcript_path(&args[1..]))?, io::BufWriter::new(io::stdout().lock()))?,
+        Some("dot") => export::write_dot(&load(&transcript_path(&args[1..]))?, io::BufWriter::new(io::stdout().lock()))?,
+        Some("export") => {
+            let target = args.get(1).ok_or("export needs a target directory")?;
+            export::materialize(&load(&transcript_path(&args[2..]))?, std::path::Path::new(target))?
+        }
+        Some("transcript") => generate_transcript(&args[1..])?,
+        Some("cleanup") => print_cleanup(&load(&transcript_path(&args[1..]))?, &args[1..])?,
+        Some("lint") => run_lint(&transcript_path(&args[1..]), has_flag(&args[1..], "--strict"))?,
+        Some("sizes") => print_sizes(&load(&transcript_path(&args[1..]))?, &args[1..])?,
+        Some("answers") => print_answers(&load(&transcript_path(&args[1..]))?),
+        _ => {
+            let file_system = load(&transcript_path(&args))?;
+            print_treemap(&file_system, &args)?;
+            println!();
+            print_answers(&file_system);
+        }
+    }
+    Ok(())
+}
+
+fn flag_value<'a>(args: &'a [String], flag: &str) -> Option<&'a str> {
+    let position = args.iter().position(|arg| arg == flag)?;
+    args.get(position + 1).map(|value| value.as_str())
+}
+
+fn has_flag(args: &[String], flag: &str) -> bool {
+    args.iter().any(|arg| arg == flag)
+}
+
+/// `seven transcript <dir> [--bfs] [--max-depth N] [--redundant-ls] [--revisit] [--check]` prints a
+/// transcript of a real directory, or with `--check` reconstructs it and compares against the disk
+fn generate_transcript(args: &[String]) -> Result<(), Box<dyn Error>> {
+    let directory = std::path::Path::new(args.first().ok_or("transcript needs a directory")?);
+    let options = transcript::Options {
+        breadth_first: has_flag(args, "--bfs"),
+        max_depth: flag_value(args, "--max-depth").map(|depth| depth.parse()).transpose()?,
+        redundant_ls: has_flag(args, "--redundant-ls"),
+        revisit: has_flag(args, "--revisit"),
+    };
+    let lines = transcript::generate(directory, &options)?;
+    if has_flag(args, "--check") {
+        let instructions = parse_instructions(lines.into_iter().map(Ok).collect())?;
+        let file_system = interpret_instructions(instructions)?;
+        let (files, bytes) = transcript::check_round_trip(directory, &options, &file_system)?;
+        println!("round trip ok: {} files, {} bytes", files, bytes);
+    } else {
+        for line in lines {
+            println!("{}", line);
+        }
+    }
+    Ok(())
+}
+
+/// `seven lint [transcript] [--strict]` prints every diagnostic and fails when there are errors, or
+/// with `--strict` when there are any diagnostics at all
+fn run_lint(path: &str, strict: bool) -> Result<(), Box<dyn Error>> {
+    let lines = BufReader::new(File::open(path)?).lines().collect::<Result<Vec<String>, _>>()?;
+    let diagnostics = lint::lint(&lines, strict);
+    for diagnostic in &diagnostics {
+        println!("{}:{}", path, diagnostic);
+    }
+    let errors = diagnostics.iter().filter(|diagnostic| diagnostic.severity == lint::Severity::Error).count();
+    let warnings = diagnostics.len() - errors;
+    println!("{} errors, {} warnings", errors, warnings);
+    if errors > 0 {
+        return Err(format!("{} failed lint", path).into());
+    }
+    Ok(())
+}
+
+/// reconstructs the filesystem described by a terminal transcript
+fn load(path: &str) -> Result<FileSystem, Box<dyn Error>> {
+    let file = File::open(path)?;
     let reader = BufReader::new(file);
-    let mut lines: Vec<Result<String, io::Error>> = reader.lines().collect();
-    lines.reverse();
+    let lines: Vec<Result<String, io::Error>> = reader.lines().collect();
     let instructions = parse_instructions(lines)?;
-    let file_system: FileSystem = interpret_instructions(instructions);
+    let file_system: FileSystem = interpret_instructions(instructions).map_err(|e| e.to_string())?;
+    Ok(file_system)
+}
+
+fn print_answers(file_system: &FileSystem) {
+    let small_directories: u64 = file_system.directories_at_most(SMALL_DIRECTORY_LIMIT).map(|(_, size)| size).sum();
+    println!("answer 1: {}", small_directories);
+
+    let used = file_system.total_size(file_system.root);
+    let to_free = REQUIRED_FREE_SPACE.saturating_sub(TOTAL_DISK_SPACE.saturating_sub(used));
+    match file_system.directories_at_least(to_free).next() {
+        Some((index, size)) => println!("answer 2: {} (/{})", size, file_system.path_of(index).join("/")),
+        None => println!("answer 2: no single directory frees {} bytes", to_free),
+    }
+}
+
+/// `seven [transcript] [--depth N] [--min-size N] [--width N]` draws the disk usage tree
+fn print_treemap(file_system: &FileSystem, args: &[String]) -> Result<(), Box<dyn Error>> {
+    let defaults = treemap::Options::default();
+    let options = treemap::Options {
+        max_depth: flag_value(args, "--depth").map_or(Ok(defaults.max_depth), str::parse)?,
+        min_size: flag_value(args, "--min-size").map_or(Ok(defaults.min_size), str::parse)?,
+        width: flag_value(args, "--width").map_or(Ok(defaults.width), str::parse)?,
+    };
+    treemap::render(file_system, &options, &mut io::BufWriter::new(io::stdout().lock()))?;
+    Ok(())
+}
+
+/// `seven cleanup [transcript] [--disk N] [--free N] [--protect PATH]... [--solver auto|exact|greedy]`
+/// prints the smallest set of entries to delete to get `--free` bytes free on a `--disk` byte disk
+fn print_cleanup(file_system: &FileSystem, args: &[String]) -> Result<(), Box<dyn Error>> {
+    let disk: u64 = flag_value(args, "--disk").map_or(Ok(TOTAL_DISK_SPACE), str::parse)?;
+    let free: u64 = flag_value(args, "--free").map_or(Ok(REQUIRED_FREE_SPACE), str::parse)?;
+    let solver: cleanup::Solver = flag_value(args, "--solver").map_or(Ok(cleanup::Solver::Auto), str::parse)?;
+    let protected: Vec<Path> = args
+        .windows(2)
+        .filter(|pair| pair[0] == "--protect")
+        .map(|pair| pair[1].split('/').filter(|segment| !segment.is_empty()).map(String::from).collect())
+        .collect();
+
+    let used = file_system.total_size(file_system.root);
+    let need = free.saturating_sub(disk.saturating_sub(used));
+    println!("{} of {} bytes used, {} to free", used, disk, need);
+    let plan = cleanup::plan(file_system, need, &protected, solver)?;
+    for deletion in &plan.deletions {
+        let suffix = if deletion.directory { "/" } else { "" };
+        println!("{:>12}  /{}{}", deletion.size, deletion.path.join("/"), suffix);
+    }
+    let solver = if plan.exact { "exact" } else { "greedy" };
+    println!("reclaimed {} bytes in {} entries, {} more than needed ({})", plan.reclaimed, plan.deletions.len(), plan.reclaimed - need, solver);
+    Ok(())
+}
 
-    println!("instructions: {:?}", instructions);
-    todo!()
+/// `seven sizes [transcript] (--largest N | --at-most N | --at-least N | --subtree PATH)`
+fn print_sizes(file_system: &FileSystem, args: &[String]) -> Result<(), Box<dyn Error>> {
+    let print = |(index, size): (usize, u64)| println!("{}\t/{}", size, file_system.path_of(index).join("/"));
+    if let Some(n) = flag_value(args, "--largest") {
+        file_system.largest_directories(n.parse()?).into_iter().for_each(print);
+    } else if let Some(limit) = flag_value(args, "--at-most") {
+        file_system.directories_at_most(limit.parse()?).for_each(print);
+    } else if let Some(limit) = flag_value(args, "--at-least") {
+        file_system.directories_at_least(limit.parse()?).for_each(print);
+    } else if let Some(path) = flag_value(args, "--subtree") {
+        let path: Path = path.split('/').filter(|segment| !segment.is_empty()).map(String::from).collect();
+        let size = file_system.subtree_size(&path).ok_or_else(|| format!("/{} isn't in the transcript", path.join("/")))?;
+        println!("{}\t/{}", size, path.join("/"));
+    } else {
+        return Err("sizes needs --largest, --at-most, --at-least or --subtree".into());
+    }
+    Ok(())
 }
 
-fn interpret_instructions(instructions: Vec<Instruction>) -> FileSystem {
-    let root = 0;
-    let mut filesystem = FileSystem::new(root);
-    let mut cursor = FileSystemCursor::new(root);
+fn interpret_instructions(instructions: Vec<Instruction>) -> io::Result<FileSystem> {
+    let mut filesystem = FileSystem::new();
+    let mut cursor = FileSystemCursor::new(filesystem.root);
     for instruction in instructions {
         match instruction {
-            Instruction::Cd { dir } if dir.eq("./") => {
-                cursor.pop()
-            }
-            Instruction::Cd { dir } if dir.eq("/") => {
-                cursor.root()
-            }
-
             Instruction::Cd { dir } => {
-                let last = cursor.parent.last();
-                cursor.push(dir, last);
-                if let Some((index, current_entry)) = filesystem.get(cursor.path()) {} else {
-                    filesystem.insert_dir(cursor.path(), cursor.parent.last());
+                cursor.cd(&filesystem, &dir)?;
+            }
+            Instruction::Ls { output, children } => {
+                let parent = cursor.current();
+                for file in output {
+                    filesystem.insert_file(cursor.child_path(&file.name), parent, file.size);
+                }
+                for child in children {
+                    filesystem.insert_dir(cursor.child_path(&child.name), parent);
                 }
             }
-            Instruction::Ls { output } => {}
         }
     }
-    todo!()
+    Ok(filesystem)
 }
 
 fn parse_instructions(lines: Vec<Result<String, io::Error>>) -> Result<Vec<Instruction>, Box<dyn Error>> {
-    let mut files_buffer: Vec<FileSize> = Vec::with_capacity(64);
-    let mut directories_buffer: Vec<ChildDir> = Vec::with_capacity(64);
     let mut instructions: Vec<Instruction> = Vec::with_capacity(128);
     for line in lines {
         let line = line?;
-        if line.starts_with("$") {
+        if line.starts_with('$') {
             let instruction: Instruction = line.parse()?;
-            let instruction = match instruction {
-                Instruction::Cd { dir } => {
-                    Instruction::Cd {
-                        dir: dir.clone()
-                    }
-                }
-                Instruction::Ls { .. } => {
-                    let result = Instruction::Ls {
-                        output: files_buffer.clone(),
-                        children: directories_buffer.clone(),
-                    };
-                    files_buffer.clear();
-                    directories_buffer.clear();
-                    result
-                }
-            };
-            println!("instruction: {:?}", instruction);
-            instructions.push(instruction.clone())
-        } else if line.starts_with(|c: char| c.is_ascii_digit()) {
-            let file_size: FileSize = line.parse()?;
-            files_buffer.push(file_size);
-        } else if line.starts_with("dir") {
-            let child_dir: ChildDir = line.parse()?;
-            directories_buffer.push(child_dir);
+            instructions.push(instruction)
+        } else if let Some(Instruction::Ls { output, children }) = instructions.last_mut() {
+            if line.starts_with(|c: char| c.is_ascii_digit()) {
+                let file_size: FileSize = line.parse()?;
+                output.push(file_size);
+            } else if line.starts_with("dir") {
+                let child_dir: ChildDir = line.parse()?;
+                children.push(child_dir);
+            }
         }
     }
     Ok(instructions)
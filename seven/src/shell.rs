@@ -0,0 +1,245 @@
+use std::io;
+use std::io::{BufRead, ErrorKind, Write};
+use crate::{FileSystem, FileSystemCursor, FileSystemEntry};
+
+const HELP: &str = "\
+commands:
+  cd <dir>                      change directory (absolute, relative, . and ..)
+  ls [dir]                      list a directory in transcript format
+  pwd                           print the current directory
+  du [-h] [--max-depth N] [dir] total size of every directory below dir
+  find [dir] -size [+-]N[ckMG]  files larger (+), smaller (-) or exactly N bytes
+  tree [dir]                    draw the directory tree with sizes
+  help                          show this help
+  exit                          leave the shell";
+
+/// Reads commands from `input` until end of input or `exit`, writing prompts and results to `output`
+pub fn run<R: BufRead, W: Write>(filesystem: &FileSystem, input: R, mut output: W) -> io::Result<()> {
+    let mut cursor = FileSystemCursor::new(filesystem.root);
+    write!(output, "$ ")?;
+    output.flush()?;
+    for line in input.lines() {
+        let line = line?;
+        let line = line.trim();
+        let (command, rest) = line.split_once(char::is_whitespace).unwrap_or((line, ""));
+        let rest = rest.trim();
+        let result = match command {
+            "" => Ok(()),
+            "exit" | "quit" => return Ok(()),
+            "help" => writeln!(output, "{}", HELP),
+            "pwd" => writeln!(output, "/{}", cursor.path().join("/")),
+            "cd" => cursor.cd(filesystem, if rest.is_empty() { "/" } else { rest }),
+            "ls" => resolve(filesystem, &cursor, rest).and_then(|dir| ls(filesystem, &dir, &mut output)),
+            "du" => du(filesystem, &cursor, rest, &mut output),
+            "find" => find(filesystem, &cursor, rest, &mut output),
+            "tree" => resolve(filesystem, &cursor, rest).and_then(|dir| tree(filesystem, &dir, &mut output)),
+            other => Err(io::Error::new(ErrorKind::InvalidInput, format!("{}: command not found", other))),
+        };
+        if let Err(e) = result {
+            if e.kind() == ErrorKind::BrokenPipe {
+                return Err(e);
+            }
+            writeln!(output, "{}", e)?;
+        }
+        write!(output, "$ ")?;
+        output.flush()?;
+    }
+    writeln!(output)
+}
+
+/// a cursor positioned at `dir` relative to the current one, or the current one when `dir` is empty
+fn resolve(filesystem: &FileSystem, cursor: &FileSystemCursor, dir: &str) -> io::Result<FileSystemCursor> {
+    let mut target = cursor.clone();
+    if !dir.is_empty() {
+        target.cd(filesystem, dir)?;
+    }
+    Ok(target)
+}
+
+/// splits arguments on whitespace outside quotes, leaving the quotes in place for `cd` to interpret
+fn split_words(args: &str) -> Vec<String> {
+    let mut words = Vec::new();
+    let mut current = String::new();
+    let mut quote: Option<char> = None;
+    let mut escaped = false;
+    for c in args.chars() {
+        match (c, quote) {
+            _ if escaped => escaped = false,
+            ('\\', _) => escaped = true,
+            ('"', None) | ('\'', None) => quote = Some(c),
+            (c, Some(open)) if c == open => quote = None,
+            (c, None) if c.is_whitespace() => {
+                if !current.is_empty() {
+                    words.push(std::mem::take(&mut current));
+                }
+                continue;
+            }
+            _ => {}
+        }
+        current.push(c);
+    }
+    if !current.is_empty() {
+        words.push(current);
+    }
+    words
+}
+
+fn display_path(path: &[String]) -> String {
+    format!("/{}", path.join("/"))
+}
+
+fn ls<W: Write>(filesystem: &FileSystem, dir: &FileSystemCursor, output: &mut W) -> io::Result<()> {
+    for (name, index) in filesystem.sorted_children(dir.current()) {
+        match &filesystem.entries[index] {
+            FileSystemEntry::DirectoryEntry(_) => writeln!(output, "dir {}", name)?,
+            FileSystemEntry::FileEntry(file) => writeln!(output, "{} {}", file.size, name)?,
+        }
+    }
+    Ok(())
+}
+
+/// sizes with a K, M or G suffix in powers of 1024, like `du -h`
+pub fn human_readable(size: u64) -> String {
+    const UNITS: [&str; 4] = ["K", "M", "G", "T"];
+    if size < 1024 {
+        return size.to_string();
+    }
+    let mut value = size as f64 / 1024.0;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+    if value < 10.0 {
+        format!("{:.1}{}", value, UNITS[unit])
+    } else {
+        format!("{:.0}{}", value, UNITS[unit])
+    }
+}
+
+fn du<W: Write>(filesystem: &FileSystem, cursor: &FileSystemCursor, args: &str, output: &mut W) -> io::Result<()> {
+    let mut human = false;
+    let mut max_depth = usize::MAX;
+    let mut dir = String::new();
+    let words = split_words(args);
+    let mut words = words.iter().map(|word| word.as_str());
+    while let Some(word) = words.next() {
+        match word {
+            "-h" => human = true,
+            "--max-depth" | "-d" => max_depth = parse_number(words.next(), "--max-depth")?,
+            _ if word.starts_with("--max-depth=") => max_depth = parse_number(word.strip_prefix("--max-depth="), "--max-depth")?,
+            _ => dir = word.to_string(),
+        }
+    }
+    let start = resolve(filesystem, cursor, &dir)?;
+    du_directory(filesystem, start.current(), &mut start.path(), 0, max_depth, human, output)?;
+    Ok(())
+}
+
+/// prints directories below `directory` in post-order like `du`, returning the directory's total size
+fn du_directory<W: Write>(filesystem: &FileSystem, directory: usize, path: &mut Vec<String>, depth: usize, max_depth: usize, human: bool, output: &mut W) -> io::Result<u64> {
+    let mut total = 0;
+    for (name, index) in filesystem.sorted_children(directory) {
+        match &filesystem.entries[index] {
+            FileSystemEntry::FileEntry(file) => total += file.size,
+            FileSystemEntry::DirectoryEntry(_) => {
+                path.push(name.clone());
+                total += du_directory(filesystem, index, path, depth + 1, max_depth, human, output)?;
+                path.pop();
+            }
+        }
+    }
+    if depth <= max_depth {
+        let size = if human { human_readable(total) } else { total.to_string() };
+        writeln!(output, "{}\t{}", size, display_path(path))?;
+    }
+    Ok(total)
+}
+
+fn parse_number(word: Option<&str>, flag: &str) -> io::Result<usize> {
+    word.and_then(|word| word.parse().ok())
+        .ok_or_else(|| io::Error::new(ErrorKind::InvalidInput, format!("{} needs a number", flag)))
+}
+
+/// parses `find`'s size argument: an optional `+` or `-`, a number and an optional unit (bytes by default)
+fn parse_size_filter(arg: &str) -> io::Result<(std::cmp::Ordering, u64)> {
+    let invalid = || io::Error::new(ErrorKind::InvalidInput, format!("find: invalid size {}", arg));
+    let (ordering, rest) = match arg.as_bytes().first() {
+        Some(b'+') => (std::cmp::Ordering::Greater, &arg[1..]),
+        Some(b'-') => (std::cmp::Ordering::Less, &arg[1..]),
+        _ => (std::cmp::Ordering::Equal, arg),
+    };
+    let (digits, multiplier) = match rest.as_bytes().last() {
+        Some(b'c') => (&rest[..rest.len() - 1], 1),
+        Some(b'k') => (&rest[..rest.len() - 1], 1024),
+        Some(b'M') => (&rest[..rest.len() - 1], 1024 * 1024),
+        Some(b'G') => (&rest[..rest.len() - 1], 1024 * 1024 * 1024),
+        _ => (rest, 1),
+    };
+    let number: u64 = digits.parse().map_err(|_| invalid())?;
+    Ok((ordering, number.checked_mul(multiplier).ok_or_else(invalid)?))
+}
+
+fn find<W: Write>(filesystem: &FileSystem, cursor: &FileSystemCursor, args: &str, output: &mut W) -> io::Result<()> {
+    let mut dir = String::new();
+    let mut filter = None;
+    let words = split_words(args);
+    let mut words = words.iter().map(|word| word.as_str());
+    while let Some(word) = words.next() {
+        match word {
+            "-size" => {
+                let size = words.next().ok_or_else(|| io::Error::new(ErrorKind::InvalidInput, "find: -size needs an argument"))?;
+                filter = Some(parse_size_filter(size)?);
+            }
+            _ if word.starts_with('-') => {
+                return Err(io::Error::new(ErrorKind::InvalidInput, format!("find: unknown predicate {}", word)));
+            }
+            _ => dir = word.to_string(),
+        }
+    }
+    let start = resolve(filesystem, cursor, &dir)?;
+    let mut matches = Vec::new();
+    let mut path = start.path();
+    collect_files(filesystem, start.current(), &mut path, &mut |path, size| {
+        if filter.is_none_or(|(ordering, limit)| size.cmp(&limit) == ordering) {
+            matches.push(display_path(path));
+        }
+    });
+    for found in matches {
+        writeln!(output, "{}", found)?;
+    }
+    Ok(())
+}
+
+fn collect_files(filesystem: &FileSystem, directory: usize, path: &mut Vec<String>, visit: &mut dyn FnMut(&[String], u64)) {
+    for (name, index) in filesystem.sorted_children(directory) {
+        path.push(name.clone());
+        match &filesystem.entries[index] {
+            FileSystemEntry::FileEntry(file) => visit(path, file.size),
+            FileSystemEntry::DirectoryEntry(_) => collect_files(filesystem, index, path, visit),
+        }
+        path.pop();
+    }
+}
+
+fn tree<W: Write>(filesystem: &FileSystem, dir: &FileSystemCursor, output: &mut W) -> io::Result<()> {
+    writeln!(output, "{} ({})", display_path(&dir.path()), filesystem.total_size(dir.current()))?;
+    tree_children(filesystem, dir.current(), "", output)
+}
+
+fn tree_children<W: Write>(filesystem: &FileSystem, directory: usize, prefix: &str, output: &mut W) -> io::Result<()> {
+    let children = filesystem.sorted_children(directory);
+    for (i, (name, index)) in children.iter().enumerate() {
+        let last = i == children.len() - 1;
+        let branch = if last { "└── " } else { "├── " };
+        match &filesystem.entries[*index] {
+            FileSystemEntry::FileEntry(file) => writeln!(output, "{}{}{} ({})", prefix, branch, name, file.size)?,
+            FileSystemEntry::DirectoryEntry(_) => {
+                writeln!(output, "{}{}{}/ ({})", prefix, branch, name, filesystem.total_size(*index))?;
+                let child_prefix = format!("{}{}", prefix, if last { "    " } else { "│   " });
+                tree_children(filesystem, *index, &child_prefix, output)?;
+            }
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,236 @@
+use std::collections::VecDeque;
+use std::fs;
+use std::io;
+use std::io::ErrorKind;
+use std::path::Path;
+use crate::{FileSystem, FileSystemEntry};
+
+/// How `generate` walks the directory
+#[derive(Clone, Debug, Default)]
+pub struct Options {
+    pub breadth_first: bool,
+    /// directories deeper than this are listed by their parent but never entered
+    pub max_depth: Option<usize>,
+    /// run `ls` twice in every directory
+    pub redundant_ls: bool,
+    /// after the walk, go back into every directory with subdirectories and list it again
+    pub revisit: bool,
+}
+
+/// A directory read from disk; `listed` is false for directories beyond the depth limit
+struct Node {
+    files: Vec<(String, u64)>,
+    children: Vec<(String, Node)>,
+    listed: bool,
+}
+
+fn scan(path: &Path, depth: usize, max_depth: Option<usize>) -> io::Result<Node> {
+    let mut node = Node { files: Vec::new(), children: Vec::new(), listed: true };
+    if max_depth.is_some_and(|max_depth| depth > max_depth) {
+        node.listed = false;
+        return Ok(node);
+    }
+    for entry in fs::read_dir(path)? {
+        let entry = entry?;
+        let name = entry
+            .file_name()
+            .into_string()
+            .map_err(|name| io::Error::new(ErrorKind::InvalidData, format!("{:?} is not valid UTF-8", name)))?;
+        if name.contains('\n') {
+            return Err(io::Error::new(ErrorKind::InvalidData, format!("{:?} can't be written on one transcript line", name)));
+        }
+        // symlinks aren't followed, so a link to a parent can't make the walk loop
+        let metadata = entry.path().symlink_metadata()?;
+        if metadata.is_dir() {
+            node.children.push((name, scan(&entry.path(), depth + 1, max_depth)?));
+        } else if metadata.is_file() {
+            node.files.push((name, metadata.len()));
+        }
+    }
+    node.files.sort();
+    node.children.sort_by(|(a, _), (b, _)| a.cmp(b));
+    Ok(node)
+}
+
+/// quotes a name for `cd` when it has characters the cd parser would otherwise interpret
+fn cd_argument(name: &str) -> String {
+    let special = name.contains(['"', '\'', '\\']) || name.trim() != name || name == "." || name == "..";
+    if special {
+        format!("\"{}\"", name.replace('\\', "\\\\").replace('"', "\\\""))
+    } else {
+        name.to_string()
+    }
+}
+
+fn ls(node: &Node, transcript: &mut Vec<String>) {
+    transcript.push("$ ls".to_string());
+    let mut lines: Vec<(&String, String)> = node
+        .children
+        .iter()
+        .map(|(name, _)| (name, format!("dir {}", name)))
+        .chain(node.files.iter().map(|(name, size)| (name, format!("{} {}", size, name))))
+        .collect();
+    lines.sort();
+    transcript.extend(lines.into_iter().map(|(_, line)| line));
+}
+
+/// directories to visit, as paths of child positions from the root, in depth- or breadth-first order
+fn visit_order(root: &Node, breadth_first: bool) -> Vec<Vec<usize>> {
+    let mut order = Vec::new();
+    let mut pending: VecDeque<(Vec<usize>, &Node)> = VecDeque::from([(Vec::new(), root)]);
+    while let Some((path, node)) = if breadth_first { pending.pop_front() } else { pending.pop_back() } {
+        let children = node.children.iter().enumerate().filter(|(_, (_, child))| child.listed);
+        let children: Vec<(Vec<usize>, &Node)> = children
+            .map(|(i, (_, child))| {
+                let mut child_path = path.clone();
+                child_path.push(i);
+                (child_path, child)
+            })
+            .collect();
+        if breadth_first {
+            pending.extend(children);
+        } else {
+            pending.extend(children.into_iter().rev());
+        }
+        order.push(path);
+    }
+    order
+}
+
+/// Walks `directory` and writes the `$ cd` / `$ ls` transcript that `parse_instructions` reads back
+pub fn generate(directory: &Path, options: &Options) -> io::Result<Vec<String>> {
+    let root = scan(directory, 0, options.max_depth)?;
+    let mut order = visit_order(&root, options.breadth_first);
+    if options.revisit {
+        let revisits: Vec<Vec<usize>> = order
+            .iter()
+            .rev()
+            .filter(|path| !node_at(&root, path).children.is_empty())
+            .cloned()
+            .collect();
+        order.extend(revisits);
+    }
+
+    let mut transcript = vec!["$ cd /".to_string()];
+    let mut current: Vec<usize> = Vec::new();
+    for path in order {
+        let common = current.iter().zip(&path).take_while(|(a, b)| a == b).count();
+        for _ in common..current.len() {
+            transcript.push("$ cd ..".to_string());
+        }
+        for depth in common..path.len() {
+            let (name, _) = &node_at(&root, &path[..depth]).children[path[depth]];
+            transcript.push(format!("$ cd {}", cd_argument(name)));
+        }
+        let node = node_at(&root, &path);
+        ls(node, &mut transcript);
+        if options.redundant_ls {
+            ls(node, &mut transcript);
+        }
+        current = path;
+    }
+    Ok(transcript)
+}
+
+fn node_at<'a>(root: &'a Node, path: &[usize]) -> &'a Node {
+    path.iter().fold(root, |node, i| &node.children[*i].1)
+}
+
+/// Checks that a filesystem reconstructed from a generated transcript holds exactly the files and
+/// directories found on disk, returning the number of files and their total size
+pub fn check_round_trip(directory: &Path, options: &Options, filesystem: &FileSystem) -> io::Result<(usize, u64)> {
+    let root = scan(directory, 0, options.max_depth)?;
+    let mut expected = Vec::new();
+    flatten_disk(&root, &mut Vec::new(), &mut expected);
+    let mut actual = Vec::new();
+    flatten_filesystem(filesystem, filesystem.root, &mut Vec::new(), &mut actual);
+    expected.sort();
+    actual.sort();
+    if let Some((want, got)) = expected.iter().zip(actual.iter()).find(|(want, got)| want != got) {
+        let message = format!("round trip mismatch: disk has {:?}, reconstruction has {:?}", want, got);
+        return Err(io::Error::new(ErrorKind::InvalidData, message));
+    }
+    if expected.len() != actual.len() {
+        let message = format!("round trip mismatch: {} entries on disk, {} reconstructed", expected.len(), actual.len());
+        return Err(io::Error::new(ErrorKind::InvalidData, message));
+    }
+    let files = expected.iter().filter(|(_, size)| size.is_some()).count();
+    Ok((files, filesystem.total_size(filesystem.root)))
+}
+
+/// every entry as (path, Some(size)) for files and (path, None) for directories
+fn flatten_disk(node: &Node, path: &mut Vec<String>, out: &mut Vec<(Vec<String>, Option<u64>)>) {
+    for (name, size) in &node.files {
+        path.push(name.clone());
+        out.push((path.clone(), Some(*size)));
+        path.pop();
+    }
+    for (name, child) in &node.children {
+        path.push(name.clone());
+        out.push((path.clone(), None));
+        flatten_disk(child, path, out);
+        path.pop();
+    }
+}
+
+fn flatten_filesystem(filesystem: &FileSystem, directory: usize, path: &mut Vec<String>, out: &mut Vec<(Vec<String>, Option<u64>)>) {
+    for (name, index) in filesystem.sorted_children(directory) {
+        path.push(name.clone());
+        match &filesystem.entries[index] {
+            FileSystemEntry::FileEntry(file) => out.push((path.clone(), Some(file.size))),
+            FileSystemEntry::DirectoryEntry(_) => {
+                out.push((path.clone(), None));
+                flatten_filesystem(filesystem, index, path, out);
+            }
+        }
+        path.pop();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{interpret_instructions, parse_instructions};
+
+    /// a tree whose names need quoting in `cd`, with a directory below the depth limit
+    fn awkward_tree() -> io::Result<tempfile::TempDir> {
+        let root = tempfile::tempdir()?;
+        for directory in ["with space", " leading space", "say \"hi\"", "back\\slash", "it's", "with space/inner dir", "say \"hi\"/deep/deeper"] {
+            fs::create_dir_all(root.path().join(directory))?;
+        }
+        for (file, contents) in [
+            ("top.txt", "top"),
+            ("with space/a b.txt", "spaces"),
+            (" leading space/ x", "leading"),
+            ("say \"hi\"/q\"uote", "quote"),
+            ("back\\slash/f\\g", "backslash"),
+            ("it's/it's", "apostrophe"),
+            ("with space/inner dir/file", "inner"),
+            ("say \"hi\"/deep/deeper/bottom", "bottom"),
+        ] {
+            fs::write(root.path().join(file), contents)?;
+        }
+        Ok(root)
+    }
+
+    #[test]
+    fn awkward_names_round_trip() -> Result<(), Box<dyn std::error::Error>> {
+        let root = awkward_tree()?;
+        for max_depth in [None, Some(0), Some(1)] {
+            for (breadth_first, redundant_ls, revisit) in [(false, false, false), (true, false, false), (false, true, true)] {
+                let options = Options { breadth_first, max_depth, redundant_ls, revisit };
+                let lines = generate(root.path(), &options)?;
+                let instructions = parse_instructions(lines.into_iter().map(Ok).collect())?;
+                let filesystem = interpret_instructions(instructions)?;
+                let (files, _) = check_round_trip(root.path(), &options, &filesystem)?;
+                let expected = match max_depth {
+                    None => 8,
+                    Some(0) => 1,
+                    _ => 6,
+                };
+                assert_eq!(files, expected, "{:?}", options);
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,98 @@
+use std::io;
+use std::io::Write;
+use crate::shell::human_readable;
+use crate::{FileSystem, FileSystemEntry};
+
+/// full block followed by the partial blocks from seven eighths down to one eighth
+const BLOCKS: [char; 8] = ['█', '▉', '▊', '▋', '▌', '▍', '▎', '▏'];
+
+/// What `render` draws
+#[derive(Clone, Debug)]
+pub struct Options {
+    /// levels below the root to draw; deeper entries only count toward their ancestors
+    pub max_depth: usize,
+    /// entries smaller than this are folded into one line per directory
+    pub min_size: u64,
+    /// width of the bar in characters
+    pub width: usize,
+}
+
+impl Default for Options {
+    fn default() -> Options {
+        Options { max_depth: 3, min_size: 0, width: 20 }
+    }
+}
+
+/// a bar `width` characters wide filled to `fraction`, with eighth-block precision
+fn bar(fraction: f64, width: usize) -> String {
+    let eighths = (fraction.clamp(0.0, 1.0) * (width * 8) as f64).round() as usize;
+    let mut bar = BLOCKS[0].to_string().repeat(eighths / 8);
+    let partial = eighths % 8;
+    if partial > 0 {
+        bar.push(BLOCKS[8 - partial]);
+    }
+    let filled = bar.chars().count();
+    bar.extend(std::iter::repeat_n(' ', width - filled));
+    bar
+}
+
+fn percent(part: u64, whole: u64) -> f64 {
+    if whole == 0 { 0.0 } else { part as f64 * 100.0 / whole as f64 }
+}
+
+/// Draws the filesystem like `ncdu`: every directory's entries largest first, each with its size,
+/// its share of the parent and of the whole tree, and a bar proportional to its share of the parent
+pub fn render<W: Write>(filesystem: &FileSystem, options: &Options, output: &mut W) -> io::Result<()> {
+    let total = filesystem.total_size(filesystem.root);
+    writeln!(output, "{:>7} {:>6} {:>6} [{}] /", human_readable(total), "100.0%", "100.0%", bar(1.0, options.width))?;
+    render_directory(filesystem, filesystem.root, total, 1, options, output)
+}
+
+fn render_directory<W: Write>(filesystem: &FileSystem, directory: usize, total: u64, depth: usize, options: &Options, output: &mut W) -> io::Result<()> {
+    if depth > options.max_depth {
+        return Ok(());
+    }
+    let parent_size = filesystem.total_size(directory);
+    let mut children: Vec<(&String, usize, u64)> = filesystem
+        .sorted_children(directory)
+        .into_iter()
+        .map(|(name, index)| (name, index, filesystem.total_size(index)))
+        .collect();
+    children.sort_by(|(a_name, _, a_size), (b_name, _, b_size)| b_size.cmp(a_size).then_with(|| a_name.cmp(b_name)));
+    let indent = "  ".repeat(depth);
+    let (shown, hidden): (Vec<_>, Vec<_>) = children.into_iter().partition(|(_, _, size)| *size >= options.min_size);
+    for (name, index, size) in shown {
+        let of_parent = percent(size, parent_size);
+        let directory = matches!(filesystem.entries[index], FileSystemEntry::DirectoryEntry(_));
+        let suffix = if directory { "/" } else { "" };
+        writeln!(
+            output,
+            "{:>7} {:>5.1}% {:>5.1}% [{}] {}{}{}",
+            human_readable(size),
+            of_parent,
+            percent(size, total),
+            bar(of_parent / 100.0, options.width),
+            indent,
+            name,
+            suffix
+        )?;
+        if directory {
+            render_directory(filesystem, index, total, depth + 1, options, output)?;
+        }
+    }
+    if !hidden.is_empty() {
+        let size: u64 = hidden.iter().map(|(_, _, size)| size).sum();
+        let of_parent = percent(size, parent_size);
+        writeln!(
+            output,
+            "{:>7} {:>5.1}% {:>5.1}% [{}] {}({} smaller entries)",
+            human_readable(size),
+            of_parent,
+            percent(size, total),
+            bar(of_parent / 100.0, options.width),
+            indent,
+            hidden.len()
+        )?;
+    }
+    Ok(())
+}
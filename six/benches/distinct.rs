@@ -0,0 +1,40 @@
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion};
+use six::bitmask::{find_distinct_bytes, find_distinct_skip, find_distinct_xor};
+use six::generator::{plant_marker, XorShift};
+use six::find_distinct;
+
+fn distinct_window(c: &mut Criterion) {
+    let data = std::fs::read(concat!(env!("CARGO_MANIFEST_DIR"), "/input.txt")).expect("failed to read input");
+    let data = data.trim_ascii_end().to_vec();
+    let chars: Vec<char> = data.iter().map(|b| *b as char).collect();
+    let mut group = c.benchmark_group("find_distinct");
+    for count in [4, 14] {
+        group.bench_with_input(BenchmarkId::new("hash_set", count), &count, |b, count| {
+            b.iter(|| find_distinct(black_box(&chars), *count))
+        });
+        group.bench_with_input(BenchmarkId::new("xor_mask", count), &count, |b, count| {
+            b.iter(|| find_distinct_xor(black_box(&data), *count))
+        });
+        group.bench_with_input(BenchmarkId::new("skip_ahead", count), &count, |b, count| {
+            b.iter(|| find_distinct_skip(black_box(&data), *count))
+        });
+    }
+    group.finish();
+
+    // a 26-bit mask can't hold a window of 64, so wide windows compare the set against the byte
+    // frequency table on a stream over every byte value
+    let alphabet: Vec<u8> = (0..=255).collect();
+    let data = plant_marker(&mut XorShift::new(1), &alphabet, 64, 4096, 4096).expect("failed to generate a stream");
+    let chars: Vec<char> = data.iter().map(|b| *b as char).collect();
+    let mut group = c.benchmark_group("find_distinct_wide");
+    group.bench_with_input(BenchmarkId::new("hash_set", 64), &64, |b, count| {
+        b.iter(|| find_distinct(black_box(&chars), *count))
+    });
+    group.bench_with_input(BenchmarkId::new("frequency_table", 64), &64, |b, count| {
+        b.iter(|| find_distinct_bytes(black_box(&data), *count))
+    });
+    group.finish();
+}
+
+criterion_group!(benches, distinct_window);
+criterion_main!(benches);
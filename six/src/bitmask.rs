@@ -0,0 +1,74 @@
+use crate::stream::markers;
+
+/// Bit for a lowercase ASCII letter in a 26-bit set
+fn letter_bit(byte: u8) -> u32 {
+    1 << (byte - b'a')
+}
+
+fn all_lowercase(data: &[u8]) -> bool {
+    data.iter().all(u8::is_ascii_lowercase)
+}
+
+/// Rolling variant: each letter entering the window flips its bit and each letter leaving flips it back.
+/// A letter present an even number of times cancels out, so the mask has `count` bits set exactly when
+/// the window holds `count` different letters. Returns None unless the input is all lowercase ASCII.
+pub fn find_distinct_xor(data: &[u8], count: usize) -> Option<usize> {
+    if count == 0 || count > 26 || data.len() < count || !all_lowercase(data) {
+        return None;
+    }
+    let mut mask = 0u32;
+    for byte in &data[..count - 1] {
+        mask ^= letter_bit(*byte);
+    }
+    for i in count - 1..data.len() {
+        mask ^= letter_bit(data[i]);
+        if mask.count_ones() as usize == count {
+            return Some(i + 1);
+        }
+        mask ^= letter_bit(data[i + 1 - count]);
+    }
+    None
+}
+
+/// Skip-ahead variant: checks a candidate window from its end backwards, and on the first repeated
+/// letter moves the window start just past it, since no window containing both copies can be a marker.
+/// Returns None unless the input is all lowercase ASCII.
+pub fn find_distinct_skip(data: &[u8], count: usize) -> Option<usize> {
+    if !all_lowercase(data) {
+        return None;
+    }
+    skip_ahead(data, count)
+}
+
+/// the skip-ahead search on input already known to be lowercase
+fn skip_ahead(data: &[u8], count: usize) -> Option<usize> {
+    if count == 0 || count > 26 {
+        return None;
+    }
+    let mut start = 0;
+    'windows: while start + count <= data.len() {
+        let mut seen = 0u32;
+        for j in (start..start + count).rev() {
+            let bit = letter_bit(data[j]);
+            if seen & bit != 0 {
+                start = j + 1;
+                continue 'windows;
+            }
+            seen |= bit;
+        }
+        return Some(start + count);
+    }
+    None
+}
+
+/// Uses the skip-ahead bitmask search for lowercase ASCII input and falls back to the byte frequency
+/// table of `stream::MarkerDetector` for anything else; either way the result is a byte offset
+pub fn find_distinct_bytes(data: &[u8], count: usize) -> Option<usize> {
+    if count == 0 {
+        return None;
+    }
+    if all_lowercase(data) {
+        return skip_ahead(data, count);
+    }
+    markers(data.iter().copied(), count, false).next()
+}
@@ -0,0 +1,172 @@
+use std::io;
+use std::io::ErrorKind;
+use crate::bitmask::{find_distinct_bytes, find_distinct_skip, find_distinct_xor};
+use crate::find_distinct;
+use crate::stream::find_marker;
+
+/// Small deterministic xorshift generator so generated streams can be reproduced from a seed
+#[derive(Clone, Debug)]
+pub struct XorShift(u64);
+
+impl XorShift {
+    pub fn new(seed: u64) -> XorShift {
+        XorShift(seed.max(1))
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        let mut x = self.0;
+        x ^= x << 13;
+        x ^= x >> 7;
+        x ^= x << 17;
+        self.0 = x;
+        x
+    }
+
+    /// a number in `0..bound`
+    pub fn below(&mut self, bound: usize) -> usize {
+        (self.next_u64() % bound as u64) as usize
+    }
+
+    pub fn pick(&mut self, choices: &[u8]) -> u8 {
+        choices[self.below(choices.len())]
+    }
+}
+
+/// Builds a datastream of `length` bytes over `alphabet` whose first window of `window` distinct
+/// bytes ends exactly at `offset`, i.e. the answer `find_distinct` should give for it
+pub fn plant_marker(rng: &mut XorShift, alphabet: &[u8], window: usize, offset: usize, length: usize) -> io::Result<Vec<u8>> {
+    let mut alphabet = alphabet.to_vec();
+    alphabet.sort_unstable();
+    alphabet.dedup();
+    if window == 0 || window > alphabet.len() {
+        return Err(invalid_input("the window must be between 1 and the alphabet size"));
+    }
+    if offset < window || offset > length {
+        return Err(invalid_input("the marker must end between the window size and the stream length"));
+    }
+    if window == 1 && offset != 1 {
+        return Err(invalid_input("every single byte is a marker of size 1"));
+    }
+
+    let prefix = offset - window;
+    let mut data = Vec::with_capacity(length);
+    for i in 0..prefix {
+        let mut byte = rng.pick(&alphabet);
+        if i + 1 >= window {
+            // the window ending at this byte must repeat something, so reuse one of its other bytes
+            // whenever they are all different and the random pick would complete a marker
+            let previous = &data[i + 1 - window..i];
+            if all_different(previous) && !previous.contains(&byte) {
+                byte = previous[rng.below(previous.len())];
+            }
+        }
+        data.push(byte);
+    }
+
+    // starting the marker with the byte right before it makes every window that ends inside the
+    // marker contain that byte twice, so none of them can be an earlier marker
+    let mut marker = Vec::with_capacity(window);
+    if let Some(last) = data.last() {
+        marker.push(*last);
+    }
+    let mut remaining: Vec<u8> = alphabet.iter().copied().filter(|b| !marker.contains(b)).collect();
+    while marker.len() < window {
+        let chosen = remaining.swap_remove(rng.below(remaining.len()));
+        marker.push(chosen);
+    }
+    data.extend(marker);
+
+    while data.len() < length {
+        data.push(rng.pick(&alphabet));
+    }
+    Ok(data)
+}
+
+fn all_different(bytes: &[u8]) -> bool {
+    let mut seen = [false; 256];
+    bytes.iter().all(|b| !std::mem::replace(&mut seen[*b as usize], true))
+}
+
+fn invalid_input(message: &str) -> io::Error {
+    io::Error::new(ErrorKind::InvalidInput, message)
+}
+
+/// Generates `rounds` random streams with planted markers and checks that the streaming, bitmask
+/// and naive implementations all find the planted offset, describing the first disagreement
+pub fn fuzz(rounds: usize, seed: u64) -> Result<(), String> {
+    let mut rng = XorShift::new(seed);
+    let lowercase: Vec<u8> = (b'a'..=b'z').collect();
+    for round in 0..rounds {
+        let alphabet: Vec<u8> = if rng.below(2) == 0 {
+            let size = 1 + rng.below(lowercase.len());
+            lowercase[..size].to_vec()
+        } else {
+            let size = 1 + rng.below(64);
+            (0..size).map(|_| rng.below(256) as u8).collect()
+        };
+        let mut distinct = alphabet.clone();
+        distinct.sort_unstable();
+        distinct.dedup();
+        let window = 1 + rng.below(distinct.len());
+        let offset = if window == 1 { 1 } else { window + rng.below(2000) };
+        let length = offset + rng.below(200);
+        let data = plant_marker(&mut rng, &alphabet, window, offset, length).map_err(|e| e.to_string())?;
+
+        let expected = Some(offset);
+        let chars: Vec<char> = data.iter().map(|b| *b as char).collect();
+        let mut results = vec![
+            ("naive", find_distinct(&chars, window)),
+            ("streaming", find_marker(&data[..], window).map_err(|e| e.to_string())?),
+            ("bytes", find_distinct_bytes(&data, window)),
+        ];
+        if data.iter().all(u8::is_ascii_lowercase) {
+            results.push(("xor mask", find_distinct_xor(&data, window)));
+            results.push(("skip ahead", find_distinct_skip(&data, window)));
+        }
+        for (name, result) in results {
+            if result != expected {
+                return Err(format!(
+                    "round {}: {} found {:?} instead of {:?} for window {} in {:?}",
+                    round,
+                    name,
+                    result,
+                    expected,
+                    window,
+                    String::from_utf8_lossy(&data)
+                ));
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn implementations_agree() {
+        assert_eq!(fuzz(500, 0x5EED), Ok(()));
+    }
+
+    #[test]
+    fn plants_the_marker_at_the_offset() {
+        let mut rng = XorShift::new(7);
+        let lowercase: Vec<u8> = (b'a'..=b'z').collect();
+        for (alphabet, window, offset, length) in [(&lowercase[..4], 4, 4, 4), (&lowercase[..], 4, 1000, 1200), (&lowercase[..14], 14, 3000, 3000), (b"ab", 2, 2, 10), (b"x", 1, 1, 5)] {
+            let data = plant_marker(&mut rng, alphabet, window, offset, length).unwrap();
+            assert_eq!(data.len(), length);
+            assert!(data.iter().all(|byte| alphabet.contains(byte)));
+            assert_eq!(find_marker(&data[..], window).unwrap(), Some(offset), "window {} in {:?}", window, String::from_utf8_lossy(&data));
+        }
+    }
+
+    #[test]
+    fn rejects_impossible_markers() {
+        let mut rng = XorShift::new(7);
+        for (alphabet, window, offset, length) in [(&b"abc"[..], 0, 3, 3), (b"abc", 4, 4, 4), (b"abc", 3, 2, 5), (b"abc", 3, 6, 5), (b"abc", 1, 2, 5)] {
+            let error = plant_marker(&mut rng, alphabet, window, offset, length).unwrap_err();
+            assert_eq!(error.kind(), ErrorKind::InvalidInput);
+        }
+    }
+}
@@ -0,0 +1,17 @@
+use std::collections::HashSet;
+
+pub mod bitmask;
+pub mod generator;
+pub mod stream;
+pub mod unicode;
+
+/// Reference implementation checking every window with a fresh set
+pub fn find_distinct(vec: &[char], count: usize) -> Option<usize> {
+    for (i, chunk) in vec.windows(count).enumerate() {
+        let set: HashSet<&char> = HashSet::from_iter(chunk);
+        if set.len() == count {
+            return Some(count + i);
+        }
+    }
+    None
+}
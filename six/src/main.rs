@@ -1,15 +1,51 @@
-use std::collections::HashSet;
 use std::error::Error;
 use std::fs::File;
 use std::io::{BufReader, Read};
+use six::bitmask::find_distinct_bytes;
+use six::find_distinct;
+use six::generator::{fuzz, plant_marker, XorShift};
+use six::stream::{find_marker, markers, segment};
+use six::unicode::{find_marker_in, Mode};
 
 fn main() -> Result<(), Box<dyn Error>>{
-    let mut file = File::open("./six/input.txt")?;
-    let mut string = String::new();
-     file.read_to_string(&mut string)?;
-    let vec: Vec<char> = string.chars().collect();
-    let sol_one = find_distinct(&vec, 4);
-    let sol_two = find_distinct(&vec, 14);
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    match args.first().map(|arg| arg.as_str()) {
+        Some("markers") => return print_markers(&args),
+        Some("frames") => return print_frames(&args),
+        Some("generate") => return generate(&args),
+        Some("fuzz") => {
+            let rounds: usize = args.get(1).map_or(Ok(1000), |rounds| rounds.parse())?;
+            fuzz(rounds, 0x5EED)?;
+            println!("all implementations agree on {} generated streams", rounds);
+            return Ok(());
+        }
+        _ => {}
+    }
+    if let Some(mode) = flag_value(&args, "--mode") {
+        let mode: Mode = mode.parse()?;
+        let text = std::fs::read_to_string(input_path(&args))?;
+        println!("solution 1: {:?}", find_marker_in(&text, 4, mode));
+        println!("solution 2: {:?}", find_marker_in(&text, 14, mode));
+        return Ok(());
+    }
+    let naive = args.iter().any(|arg| arg == "--naive");
+    let bitmask = args.iter().any(|arg| arg == "--bitmask");
+    let (sol_one, sol_two) = if bitmask {
+        let data = std::fs::read("./six/input.txt")?;
+        let data = data.trim_ascii_end();
+        (find_distinct_bytes(data, 4), find_distinct_bytes(data, 14))
+    } else if naive {
+        let mut file = File::open("./six/input.txt")?;
+        let mut string = String::new();
+        file.read_to_string(&mut string)?;
+        let vec: Vec<char> = string.chars().collect();
+        (find_distinct(&vec, 4), find_distinct(&vec, 14))
+    } else {
+        (
+            find_marker(BufReader::new(File::open("./six/input.txt")?), 4)?,
+            find_marker(BufReader::new(File::open("./six/input.txt")?), 14)?,
+        )
+    };
     println!("solution 1: {:?}", sol_one);
     println!("solution 2: {:?}", sol_two);
 
@@ -18,12 +54,60 @@ fn main() -> Result<(), Box<dyn Error>>{
     Ok(())
 }
 
-fn find_distinct(vec: &Vec<char>, count: usize) -> Option<usize> {
-    for (i, chunk) in vec.windows(count).enumerate() {
-        let set: HashSet<&char> = HashSet::from_iter(chunk);
-        if set.len() == count {
-            return Some(count + i);
-        }
+fn input_path(args: &[String]) -> &str {
+    flag_value(args, "--input").unwrap_or("./six/input.txt")
+}
+
+fn flag_value<'a>(args: &'a [String], flag: &str) -> Option<&'a str> {
+    let position = args.iter().position(|arg| arg == flag)?;
+    args.get(position + 1).map(|value| value.as_str())
+}
+
+/// a marker length from the command line; markers need at least one symbol
+fn window_size(value: &str) -> Result<usize, Box<dyn Error>> {
+    match value.parse()? {
+        0 => Err("the window size must be at least 1".into()),
+        window => Ok(window),
     }
-    None
+}
+
+/// `six markers N [--non-overlapping] [--input path]` prints the end offset of every marker of N distinct symbols
+fn print_markers(args: &[String]) -> Result<(), Box<dyn Error>> {
+    let window = window_size(args.get(1).ok_or("markers needs a window size")?)?;
+    let non_overlapping = args.iter().any(|arg| arg == "--non-overlapping");
+    let data = std::fs::read(input_path(args))?;
+    for end in markers(data.iter().copied(), window, non_overlapping) {
+        println!("{}", end);
+    }
+    Ok(())
+}
+
+/// `six frames [--packet N] [--message N] [--input path]` splits the datastream into packets and messages
+fn print_frames(args: &[String]) -> Result<(), Box<dyn Error>> {
+    let packet_window = window_size(flag_value(args, "--packet").unwrap_or("4"))?;
+    let message_window = window_size(flag_value(args, "--message").unwrap_or("14"))?;
+    let data = std::fs::read(input_path(args))?;
+    for frame in segment(&data, packet_window, message_window) {
+        println!(
+            "{:?} marker {:?} body {:?}: {}",
+            frame.kind,
+            frame.marker,
+            frame.body,
+            String::from_utf8_lossy(&data[frame.body.clone()]).trim_end()
+        );
+    }
+    Ok(())
+}
+
+/// `six generate --window N --offset N [--length N] [--alphabet abc..] [--seed N]` prints a datastream
+/// whose first marker of N distinct symbols ends at the given offset
+fn generate(args: &[String]) -> Result<(), Box<dyn Error>> {
+    let window: usize = flag_value(args, "--window").unwrap_or("4").parse()?;
+    let offset: usize = flag_value(args, "--offset").ok_or("generate needs --offset")?.parse()?;
+    let length: usize = flag_value(args, "--length").map_or(Ok(offset), |length| length.parse())?;
+    let alphabet = flag_value(args, "--alphabet").unwrap_or("abcdefghijklmnopqrstuvwxyz");
+    let seed: u64 = flag_value(args, "--seed").unwrap_or("1").parse()?;
+    let data = plant_marker(&mut XorShift::new(seed), alphabet.as_bytes(), window, offset, length)?;
+    println!("{}", String::from_utf8_lossy(&data));
+    Ok(())
 }
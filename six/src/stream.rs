@@ -0,0 +1,158 @@
+use std::io;
+use std::io::Read;
+use std::ops::Range;
+
+const BUFFER_SIZE: usize = 64 * 1024;
+
+/// Tracks the last `window` bytes of a stream with a frequency table and a count of distinct
+/// bytes, so every new byte is an O(1) update instead of rebuilding a set for each window
+pub struct MarkerDetector {
+    window: usize,
+    counts: [u32; 256],
+    distinct: usize,
+    history: Vec<u8>,
+    position: usize,
+}
+
+impl MarkerDetector {
+    pub fn new(window: usize) -> MarkerDetector {
+        assert!(window > 0, "a marker needs at least one symbol");
+        MarkerDetector {
+            window,
+            counts: [0; 256],
+            distinct: 0,
+            history: vec![0; window],
+            position: 0,
+        }
+    }
+
+    /// feeds the next byte, returning true when the last `window` bytes are all different
+    pub fn push(&mut self, byte: u8) -> bool {
+        let slot = self.position % self.window;
+        if self.position >= self.window {
+            let evicted = self.history[slot] as usize;
+            self.counts[evicted] -= 1;
+            if self.counts[evicted] == 0 {
+                self.distinct -= 1;
+            }
+        }
+        self.history[slot] = byte;
+        self.counts[byte as usize] += 1;
+        if self.counts[byte as usize] == 1 {
+            self.distinct += 1;
+        }
+        self.position += 1;
+        self.distinct == self.window
+    }
+
+    /// number of bytes fed so far
+    pub fn position(&self) -> usize {
+        self.position
+    }
+
+    /// forgets everything fed so far, so the next marker can't share bytes with earlier ones
+    pub fn reset(&mut self) {
+        self.counts = [0; 256];
+        self.distinct = 0;
+        self.position = 0;
+    }
+}
+
+/// Iterator over the end offsets of every window of distinct bytes in a stream
+pub struct Markers<I> {
+    bytes: I,
+    detector: MarkerDetector,
+    offset: usize,
+    non_overlapping: bool,
+}
+
+impl<I: Iterator<Item = u8>> Iterator for Markers<I> {
+    type Item = usize;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        for byte in self.bytes.by_ref() {
+            self.offset += 1;
+            if self.detector.push(byte) {
+                if self.non_overlapping {
+                    self.detector.reset();
+                }
+                return Some(self.offset);
+            }
+        }
+        None
+    }
+}
+
+/// Every offset at which a window of `window` distinct bytes ends, counted like `find_marker`.
+/// With `non_overlapping` each marker is made of bytes after the end of the previous one.
+pub fn markers<I: IntoIterator<Item = u8>>(bytes: I, window: usize, non_overlapping: bool) -> Markers<I::IntoIter> {
+    Markers {
+        bytes: bytes.into_iter(),
+        detector: MarkerDetector::new(window),
+        offset: 0,
+        non_overlapping,
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FrameKind {
+    Packet,
+    Message,
+}
+
+/// A section of a datastream that starts with a marker; `body` runs up to the next marker
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Frame {
+    pub kind: FrameKind,
+    pub marker: Range<usize>,
+    pub body: Range<usize>,
+}
+
+/// Splits a datastream into alternating packet and message frames: a start-of-packet marker opens a
+/// packet whose body ends where the next start-of-message marker begins, and that message runs
+/// until the following start-of-packet marker. Bytes before the first marker belong to no frame.
+pub fn segment(data: &[u8], packet_window: usize, message_window: usize) -> Vec<Frame> {
+    let mut frames: Vec<Frame> = Vec::new();
+    let mut position = 0;
+    let mut kind = FrameKind::Packet;
+    loop {
+        let window = match kind {
+            FrameKind::Packet => packet_window,
+            FrameKind::Message => message_window,
+        };
+        let Some(end) = markers(data[position..].iter().copied(), window, false).next().map(|end| position + end) else {
+            break;
+        };
+        let marker = end - window..end;
+        if let Some(previous) = frames.last_mut() {
+            previous.body.end = marker.start;
+        }
+        frames.push(Frame { kind, marker, body: end..data.len() });
+        position = end;
+        kind = match kind {
+            FrameKind::Packet => FrameKind::Message,
+            FrameKind::Message => FrameKind::Packet,
+        };
+    }
+    frames
+}
+
+/// Reads `reader` in fixed-size chunks and returns the number of bytes consumed up to and including
+/// the first window of `window` distinct bytes, without reading any further than that chunk
+pub fn find_marker<R: Read>(mut reader: R, window: usize) -> io::Result<Option<usize>> {
+    let mut detector = MarkerDetector::new(window);
+    let mut buffer = vec![0u8; BUFFER_SIZE];
+    loop {
+        let read = match reader.read(&mut buffer) {
+            Ok(0) => return Ok(None),
+            Ok(read) => read,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e),
+        };
+        for byte in &buffer[..read] {
+            if detector.push(*byte) {
+                return Ok(Some(detector.position()));
+            }
+        }
+    }
+}
@@ -0,0 +1,99 @@
+use std::collections::{HashMap, VecDeque};
+use std::hash::Hash;
+use std::io;
+use std::io::ErrorKind;
+use std::str::FromStr;
+use unicode_segmentation::UnicodeSegmentation;
+use crate::stream::markers;
+
+/// What counts as one symbol of the datastream
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Mode {
+    Bytes,
+    /// Unicode scalar values, i.e. Rust `char`s
+    Scalars,
+    /// extended grapheme clusters, so a letter and its combining marks are one symbol
+    Graphemes,
+}
+
+impl FromStr for Mode {
+    type Err = io::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "bytes" => Ok(Mode::Bytes),
+            "chars" | "scalars" => Ok(Mode::Scalars),
+            "graphemes" => Ok(Mode::Graphemes),
+            _ => Err(io::Error::new(ErrorKind::InvalidInput, "mode must be bytes, chars or graphemes")),
+        }
+    }
+}
+
+/// Where a marker ends, counted in symbols of the chosen mode, in chars and in bytes.
+/// `&text[..byte_offset]` is everything up to and including the marker; in byte mode a marker
+/// can end inside a multi-byte char, and `char_offset` only counts the chars that end before it.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Marker {
+    pub symbol_offset: usize,
+    pub char_offset: usize,
+    pub byte_offset: usize,
+}
+
+/// Rolling count of distinct symbols in the last `window` symbols, for symbol types without a small
+/// fixed alphabet; bytes use the frequency table of `stream::MarkerDetector` instead
+struct DistinctWindow<T> {
+    window: usize,
+    counts: HashMap<T, usize>,
+    history: VecDeque<T>,
+}
+
+impl<T: Hash + Eq + Clone> DistinctWindow<T> {
+    fn new(window: usize) -> DistinctWindow<T> {
+        DistinctWindow {
+            window,
+            counts: HashMap::new(),
+            history: VecDeque::with_capacity(window + 1),
+        }
+    }
+
+    fn push(&mut self, symbol: T) -> bool {
+        *self.counts.entry(symbol.clone()).or_insert(0) += 1;
+        self.history.push_back(symbol);
+        if self.history.len() > self.window {
+            let evicted = self.history.pop_front().expect("history is longer than the window");
+            let count = self.counts.get_mut(&evicted).expect("evicted symbols are counted");
+            *count -= 1;
+            if *count == 0 {
+                self.counts.remove(&evicted);
+            }
+        }
+        self.counts.len() == self.window
+    }
+}
+
+/// Finds the first window of `window` distinct symbols in `text`, where a symbol is a byte, a char or a
+/// grapheme cluster depending on `mode`
+pub fn find_marker_in(text: &str, window: usize, mode: Mode) -> Option<Marker> {
+    assert!(window > 0, "a marker needs at least one symbol");
+    match mode {
+        Mode::Bytes => {
+            let byte_offset = markers(text.bytes(), window, false).next()?;
+            let char_offset = text.char_indices().take_while(|(start, c)| start + c.len_utf8() <= byte_offset).count();
+            Some(Marker { symbol_offset: byte_offset, char_offset, byte_offset })
+        }
+        Mode::Scalars => {
+            let mut detector = DistinctWindow::new(window);
+            let (index, (start, c)) = text.char_indices().enumerate().find(|(_, (_, c))| detector.push(*c))?;
+            Some(Marker { symbol_offset: index + 1, char_offset: index + 1, byte_offset: start + c.len_utf8() })
+        }
+        Mode::Graphemes => {
+            let mut detector = DistinctWindow::new(window);
+            let (index, (start, grapheme)) = text
+                .grapheme_indices(true)
+                .enumerate()
+                .find(|(_, (_, grapheme))| detector.push(*grapheme))?;
+            let byte_offset = start + grapheme.len();
+            Some(Marker { symbol_offset: index + 1, char_offset: text[..byte_offset].chars().count(), byte_offset })
+        }
+    }
+}